@@ -1,46 +1,460 @@
-use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{Datelike, NaiveDate};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 
+use crate::overlay::Overlay;
+use crate::palette::is_symbolic_color;
+use crate::shapes::Shape;
+use crate::sun::Location;
+use crate::utils::parse_duration;
+
 #[derive(Parser, Debug)]
 pub struct RawOptions {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Name of a saved layout to use instead of the other options
+    #[arg(long)]
+    pub layout: Option<String>,
+    /// Save the given options as a layout with this name
+    #[arg(long)]
+    pub save_layout: Option<String>,
     /// Url of a background image
     #[arg(long)]
     pub background_image: Option<String>,
     /// Flag to set the background to a random image
     #[arg(long)]
     pub random_image: bool,
-    /// Color of the background, if no image is set.
-    /// Accepts: "<color-name>" | "rgb(...)" | "#FFAAEE" | "hsl(...)" | "random"
+    /// Flag to color the background like the sky at the current position of the sun.
+    /// Requires a location
+    #[arg(long)]
+    pub sky: bool,
+    /// SVG file rasterized as the background, at the resolution of the display(s)
+    #[arg(long)]
+    pub svg: Option<PathBuf>,
+    /// Repeat the SVG background as tiles this many points wide, instead of
+    /// scaling it to cover the display(s)
+    #[arg(long)]
+    pub svg_tile: Option<f32>,
+    /// Color of the background, if no image is set. "dominant" and "accent" fill
+    /// the background with the most common or most vivid color of the image set
+    /// with --random-image, --background-image, --sky or --svg.
+    /// Accepts: "<color-name>" | "rgb(...)" | "#FFAAEE" | "hsl(...)" | "random" | "dominant" | "accent"
     #[arg(long)]
     pub color: Option<String>,
-    /// Message to display to the screen
+    /// Message to display to the screen, "-" reads it from stdin
     #[arg(long)]
     pub message: Option<String>,
-    /// Random quote to display to the screen
+    /// File whose contents are displayed to the screen, read every time the
+    /// wallpaper is generated
+    #[arg(long)]
+    pub message_file: Option<PathBuf>,
+    /// Shell command whose output is displayed to the screen
+    #[arg(long)]
+    pub message_cmd: Option<String>,
+    /// Time --message-cmd may run for before it is stopped.
+    /// Accepts: "<n>s" | "<n>m" | "<n>h" | "<n>d" and combinations such as "1m30s"
+    #[arg(long, value_parser = parse_duration, default_value = "10s")]
+    pub message_timeout: Duration,
+    /// Random quote to display to the screen, from the bundled and cached quotes
     #[arg(long)]
     pub random_quote: bool,
+    /// Random quote fetched from the network to display to the screen.
+    /// Fetched quotes are cached for --random-quote
+    #[arg(long)]
+    pub network_quote: bool,
+    /// File of quotes to display to the screen, in the format given by --quote-file-format
+    #[arg(long)]
+    pub quote_file: Option<PathBuf>,
+    /// Format of the quote file. "auto" detects it from the extension and contents.
+    /// Fortune files separate quotes with "%" lines, CSV files need a "text" column
+    /// and optional "author", "source" and "tags" columns, and JSON files hold a list
+    /// of {"text", "author"} objects
+    #[arg(long, default_value = "auto", value_parser = ["auto", "fortune", "csv", "json", "kindle"])]
+    pub quote_file_format: String,
+    /// Order quotes are picked from the quote file in. "sequential" continues
+    /// where the previous wallpaper stopped, "no-repeat" is random without
+    /// repeating the last --quote-no-repeat quotes
+    #[arg(long, default_value = "random", value_parser = ["random", "sequential", "no-repeat"])]
+    pub quote_order: String,
+    /// Number of recent quotes not repeated by --quote-order no-repeat
+    #[arg(long, default_value_t = 10)]
+    pub quote_no_repeat: usize,
+    /// Only use quotes with this tag, e.g. "stoicism"
+    #[arg(long)]
+    pub quote_tag: Option<String>,
+    /// Only use quotes whose author contains this name
+    #[arg(long)]
+    pub quote_author: Option<String>,
+    /// Only use quotes with at most this many characters
+    #[arg(long)]
+    pub max_quote_length: Option<usize>,
     /// Random would to display to the screen
     #[arg(long)]
     pub random_word: bool,
-    /// Color of the text, if any is displayed
-    /// Accepts: "<color-name>" | "rgb(...)" | "#FFAAEE" | "hsl(...)" | "random"
+    /// Display the current date and time, formatted by --clock-format
+    #[arg(long)]
+    pub clock: bool,
+    /// strftime format of a line of the clock, e.g. "%A" or "%-d %B %Y". Repeat for
+    /// more lines, the first is displayed larger.
+    /// Defaults to "%H:%M" followed by "%A %-d %B %Y"
+    #[arg(long)]
+    pub clock_format: Vec<String>,
+    /// Time zone of the clock, e.g. "Europe/Paris". Defaults to the local time zone
+    #[arg(long)]
+    pub time_zone: Option<String>,
+    /// todo.txt file, or Markdown file with "- [ ]" items, to display as a checklist
+    #[arg(long)]
+    pub todo: Option<PathBuf>,
+    /// Only display todo items with at least this priority, from "A" (highest) to "Z"
+    #[arg(long, value_parser = parse_priority)]
+    pub todo_priority: Option<char>,
+    /// Only display todo items with this context, e.g. "@work"
+    #[arg(long)]
+    pub todo_context: Option<String>,
+    /// Only display todo items due within this many days, including overdue items
+    #[arg(long)]
+    pub todo_due_within: Option<u32>,
+    /// Hide the todo items that are done
+    #[arg(long)]
+    pub todo_hide_done: bool,
+    /// Most todo items displayed
+    #[arg(long, default_value_t = 10)]
+    pub todo_limit: usize,
+    /// Dictionary to display the word of the day from, with its definition.
+    /// Accepts: JSON | WordNet database | tab separated file
+    #[arg(long)]
+    pub word_of_the_day: Option<PathBuf>,
+    /// Language random words are generated from.
+    /// Accepts: "fr" | path to a list of words, one per line
+    #[arg(long, default_value = "fr")]
+    pub word_lang: String,
+    /// Number of syllables of the random words, estimated from their groups of vowels
+    #[arg(long)]
+    pub word_syllables: Option<usize>,
+    /// Number of random words to display
+    #[arg(long, default_value_t = 1)]
+    pub word_count: usize,
+    /// Capitalization of the random words
+    #[arg(long, default_value = "none", value_parser = ["none", "lower", "upper", "capitalize", "title"])]
+    pub word_case: String,
+    /// Pick real words from the --word-lang list instead of generating pseudo-words
+    #[arg(long)]
+    pub real_words: bool,
+    /// Color of the text, if any is displayed. "dominant" and "accent" are the
+    /// most common and most vivid colors behind the text, adjusted to stay readable.
+    /// Accepts: "<color-name>" | "rgb(...)" | "#FFAAEE" | "hsl(...)" | "dominant" | "accent"
     #[arg(long)]
     pub text_color: Option<String>,
-    /// Size of the text characters in pixels, defaults to filling
-    /// the text's parent.
+    /// Size of the text characters, defaults to filling the text's parent.
+    /// Accepts: "<pixels>" | "<pixels>px" | "<points>pt"
+    #[arg(long)]
+    pub text_size: Option<FontSize>,
+    /// Latitude used to compute the position of the sun, defaults to the
+    /// MACGROUND_LATITUDE environment variable
+    #[arg(long, allow_negative_numbers = true)]
+    pub latitude: Option<f64>,
+    /// Longitude used to compute the position of the sun, defaults to the
+    /// MACGROUND_LONGITUDE environment variable
+    #[arg(long, allow_negative_numbers = true)]
+    pub longitude: Option<f64>,
+    /// Draw a calendar of the current month
+    #[arg(long)]
+    pub calendar: bool,
+    /// iCalendar (.ics) file whose events are marked on the calendar. Can be repeated
+    #[arg(long)]
+    pub calendar_ics: Vec<PathBuf>,
+    /// First day of the week of the calendar
+    #[arg(long, default_value = "monday", value_parser = ["monday", "sunday"])]
+    pub week_start: String,
+    /// Number of upcoming events listed beside the calendar
+    #[arg(long, default_value_t = 0)]
+    pub calendar_events: usize,
+    /// Corner of the primary display the calendar is drawn in
+    #[arg(long, default_value = "top-right", value_parser = ANCHORS)]
+    pub calendar_position: String,
+    /// Date to count the days until, as "YYYY-MM-DD" or "MM-DD" for a date of every
+    /// year, optionally followed by "=<label>". Can be repeated
+    #[arg(long, value_parser = parse_countdown)]
+    pub countdown: Vec<Countdown>,
+    /// Period whose elapsed part is drawn as a progress bar. Can be repeated
+    #[arg(long, value_parser = ["year", "quarter", "month", "day"])]
+    pub progress: Vec<String>,
+    /// Corner of the primary display the countdowns and progress bars are drawn in
+    #[arg(long, default_value = "bottom-left", value_parser = ANCHORS)]
+    pub progress_position: String,
+    /// Shape drawn over the background, described in JSON, e.g.
+    /// '{"type": "circle", "center": [100, 100], "radius": 50, "fill": "white"}'. Can be repeated
+    #[arg(long, value_parser = Shape::parse)]
+    pub shape: Vec<Shape>,
+    /// Image or SVG file drawn over the background, described in JSON, e.g.
+    /// '{"path": "logo.png", "position": "bottom-right", "scale": 0.1, "rotation": 15, "opacity": 0.8}'. Can be repeated
+    #[arg(long, value_parser = Overlay::parse)]
+    pub overlay: Vec<Overlay>,
+    /// Stretch one continuous background across all of the displays
+    #[arg(long)]
+    pub panorama: bool,
+    /// Width of the bezels between neighbouring displays in millimeters,
+    /// used to keep a panorama aligned across the gaps
+    #[arg(long, default_value_t = 0.0)]
+    pub bezel: f32,
+    /// Background used when the background cannot be created.
+    /// Accepts: "gradient" | "none" | any color accepted by --color
+    #[arg(long, default_value = "gradient")]
+    pub fallback_background: String,
+    /// Text used when the text cannot be created
+    #[arg(long, default_value = "offline-quote", value_parser = ["offline-quote", "random-word", "none"])]
+    pub fallback_text: String,
+    /// Never use the network, sources requiring it use their fallback instead
+    #[arg(long)]
+    pub offline: bool,
+    /// File the wallpaper is written to, "-" writes it to stdout without setting it.
+    /// Defaults to a new file in the application's data directory
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// Image format of the wallpaper, guessed from the extension of the output if not set
+    #[arg(long, value_parser = ["png", "jpeg", "webp", "bmp", "tiff"])]
+    pub format: Option<String>,
+    /// Quality of JPEG and WebP wallpapers, from 1 to 100. WebP is lossless at 100
+    #[arg(long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub quality: u8,
+    /// Compression of PNG wallpapers, "best" is the smallest and slowest
+    #[arg(long, default_value = "fast", value_parser = ["fast", "default", "best"])]
+    pub compression: String,
+    /// Save the wallpaper without setting it
+    #[arg(long)]
+    pub no_set: bool,
+    /// Shell command setting the wallpaper instead of the detected desktop's
+    /// setter, where "{path}" is replaced by the path of the wallpaper
+    #[arg(long)]
+    pub set_command: Option<String>,
+    /// Shell command run after the wallpaper is saved, where "{path}" is replaced
+    /// by its path. MACGROUND_* environment variables describe the wallpaper. Can be repeated
+    #[arg(long)]
+    pub post_hook: Vec<String>,
+    /// Print a JSON report of the run instead of the status message
+    #[arg(long)]
+    pub json: bool,
+    /// Seed of the random colors, quotes, real words and file name, to reproduce
+    /// a wallpaper. Generated when not set, and given in the report and to the post hooks
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Write a 16 color terminal palette matching the wallpaper, for Xresources,
+    /// kitty, Alacritty, WezTerm and as JSON and CSS variables
     #[arg(long)]
-    pub text_size: Option<u32>,
+    pub palette: bool,
+    /// Directory the palette files are written to, implies --palette.
+    /// Defaults to "palette" in the application's data directory
+    #[arg(long)]
+    pub palette_dir: Option<PathBuf>,
+    /// Number of wallpapers kept in the history, older ones are deleted. 0 keeps all
+    #[arg(long, default_value_t = 100)]
+    pub history_keep: usize,
+    /// Total size in megabytes of the wallpapers kept in the history
+    #[arg(long)]
+    pub history_max_size: Option<u64>,
+    /// Age after which wallpapers are deleted from the history.
+    /// Accepts: "<n>s" | "<n>m" | "<n>h" | "<n>d" and combinations such as "30d"
+    #[arg(long, value_parser = parse_duration)]
+    pub history_max_age: Option<Duration>,
     // Path to an otf or ttf font
     // #[arg(long)]
     // pub font: Option<String>,
 }
 
-// save layouts.
-#[derive(Serialize, Deserialize, Debug)]
+/// Lowercase extension of --output, which gives the image format without --format.
+fn output_extension(output: Option<&Path>) -> Option<String> {
+    output
+        .and_then(|path| path.extension())
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+impl RawOptions {
+    /// Checks the options that depend on each other, exiting like clap does
+    /// when they are invalid.
+    pub fn validate(&self) {
+        if self.format.is_some() {
+            return;
+        }
+        if let Some(extension) = output_extension(self.output.as_deref()) {
+            if OutputFormat::from_name(&extension).is_none() {
+                RawOptions::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "Unsupported image format \".{extension}\" of --output. Use a png, jpeg, \
+                             jpg, webp, bmp, tiff or tif extension, or set --format"
+                        ),
+                    )
+                    .exit();
+            }
+        }
+    }
+}
+
+fn parse_priority(raw_priority: &str) -> Result<char, String> {
+    let priority = raw_priority.trim().to_ascii_uppercase();
+    match priority.chars().collect::<Vec<char>>()[..] {
+        [priority] if priority.is_ascii_uppercase() => Ok(priority),
+        _ => Err(format!(
+            "Invalid priority {raw_priority}, expected a letter"
+        )),
+    }
+}
+
+fn parse_countdown(raw_countdown: &str) -> Result<Countdown, String> {
+    let (raw_date, label) = match raw_countdown.split_once('=') {
+        Some((raw_date, label)) => (raw_date.trim(), Some(label.trim().to_string())),
+        None => (raw_countdown.trim(), None),
+    };
+
+    let date = if let Ok(date) = NaiveDate::parse_from_str(raw_date, "%Y-%m-%d") {
+        CountdownDate::Once(date)
+    } else {
+        // Parsed within a leap year to accept February 29th
+        let date = NaiveDate::parse_from_str(&format!("2000-{raw_date}"), "%Y-%m-%d")
+            .map_err(|_| format!("Invalid date {raw_date}, expected YYYY-MM-DD or MM-DD"))?;
+        CountdownDate::Yearly {
+            month: date.month(),
+            day: date.day(),
+        }
+    };
+
+    Ok(Countdown {
+        date,
+        label: label.filter(|label| !label.is_empty()),
+    })
+}
+
+/// Accepted values of [Anchor] options.
+const ANCHORS: [&str; 5] = [
+    "center",
+    "top-left",
+    "top-right",
+    "bottom-left",
+    "bottom-right",
+];
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Regenerate the wallpaper on an interval until stopped
+    Daemon(DaemonOptions),
+    /// Apply saved layouts at the times given in a schedule file
+    Schedule(ScheduleOptions),
+    /// Print the position of the sun and today's sunrise, sunset and twilight times
+    Sun,
+    /// List the previous wallpapers, newest first
+    History(HistoryOptions),
+    /// Set a previous wallpaper again
+    Revert(RevertOptions),
+    /// Add the current wallpaper to the favorites, random choices lean toward it
+    Like,
+    /// Rate the current wallpaper down, random choices lean away from it
+    Dislike,
+    /// List the liked wallpapers
+    Favorites(FavoritesOptions),
+}
+
+#[derive(Args, Debug)]
+pub struct DaemonOptions {
+    /// Time between wallpapers.
+    /// Accepts: "<n>s" | "<n>m" | "<n>h" | "<n>d" and combinations such as "1h30m"
+    #[arg(long, value_parser = parse_duration, default_value = "30m")]
+    pub every: Duration,
+    /// Time between redraws of the text on the same background, e.g. "1m" for a
+    /// clock. Redraws happen on multiples of this time, such as the start of each minute
+    #[arg(long, value_parser = parse_duration)]
+    pub text_every: Option<Duration>,
+    /// Name of the saved layout to generate, reloaded on SIGHUP.
+    /// Defaults to the options given on the command line
+    #[arg(long)]
+    pub layout: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ScheduleOptions {
+    /// Path of the schedule file, defaults to "schedule.json" in the data directory
+    #[arg(long)]
+    pub file: Option<PathBuf>,
+    /// Print the upcoming changes of layout instead of applying them
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Number of upcoming changes printed by --dry-run
+    #[arg(long, default_value_t = 10)]
+    pub count: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct HistoryOptions {
+    /// Number of wallpapers listed
+    #[arg(long, default_value_t = 20)]
+    pub count: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct RevertOptions {
+    /// Number of wallpapers to go back, as listed by the history command.
+    /// 1 is the wallpaper before the current one
+    #[arg(default_value_t = 1)]
+    pub steps: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct FavoritesOptions {
+    /// Directory the favorites are exported to, as one layout file each
+    #[arg(long)]
+    pub export: Option<PathBuf>,
+}
+
+/// Options used to generate a wallpaper. Saved to disk as layouts.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Options {
     pub background: BackgroundOptions,
     pub text: TextOptions,
     pub font: FontOptions,
+    pub display: DisplayOptions,
+    /// Location used for sun-based backgrounds
+    #[serde(default)]
+    pub location: Option<Location>,
+    #[serde(default)]
+    pub fallback: FallbackOptions,
+    /// Options of random words
+    #[serde(default)]
+    pub words: WordOptions,
+    /// Filters applied to quotes
+    #[serde(default)]
+    pub quotes: QuoteOptions,
+    /// Never use the network
+    #[serde(default)]
+    pub offline: bool,
+    /// Where and how the wallpaper is saved
+    #[serde(default)]
+    pub output: OutputOptions,
+    /// Limits of the wallpaper history
+    #[serde(default)]
+    pub history: RetentionOptions,
+    /// Shell commands run after the wallpaper is saved
+    #[serde(default)]
+    pub post_hooks: Vec<String>,
+    /// Terminal palette extracted from the wallpaper, none if [None]
+    #[serde(default)]
+    pub palette: Option<PaletteOptions>,
+    /// Calendar drawn over the background, none if [None]
+    #[serde(default)]
+    pub calendar: Option<CalendarOptions>,
+    /// Countdowns and progress bars drawn over the background, none if [None]
+    #[serde(default)]
+    pub progress: Option<ProgressOptions>,
+    /// Shapes drawn over the background, below the text
+    #[serde(default)]
+    pub shapes: Vec<Shape>,
+    /// Files drawn over the shapes, below the text
+    #[serde(default)]
+    pub overlays: Vec<Overlay>,
 }
 
 impl Options {
@@ -53,18 +467,81 @@ impl Options {
             font_size: None, // Fill the parent
         };
 
-        if let Some(color) = raw_options.color {
-            background = BackgroundOptions::Color(color);
-        } else if raw_options.random_image {
-            background = BackgroundOptions::RandomImage;
+        let image = if raw_options.random_image {
+            Some(BackgroundOptions::RandomImage)
         } else if let Some(url) = raw_options.background_image {
-            background = BackgroundOptions::Url(url);
+            Some(BackgroundOptions::Url(url))
+        } else if raw_options.sky {
+            Some(BackgroundOptions::Sky)
+        } else {
+            raw_options.svg.map(|path| BackgroundOptions::Svg {
+                path,
+                tile_width: raw_options.svg_tile,
+            })
         };
+        match (raw_options.color, image) {
+            // A symbolic color fills the background with a color of the image
+            (Some(color), Some(image)) if is_symbolic_color(&color) => {
+                background = BackgroundOptions::ImageColor {
+                    source: Box::new(image),
+                    color,
+                };
+            }
+            (Some(color), _) => background = BackgroundOptions::Color(color),
+            (None, Some(image)) => background = image,
+            (None, None) => {}
+        }
 
         if let Some(message) = raw_options.message {
             text = TextOptions::Message(message);
+        } else if let Some(path) = raw_options.message_file {
+            text = TextOptions::MessageFile(path);
+        } else if let Some(command) = raw_options.message_cmd {
+            text = TextOptions::MessageCommand {
+                command,
+                timeout: raw_options.message_timeout,
+            };
+        } else if let Some(path) = raw_options.quote_file {
+            text = TextOptions::QuoteFile {
+                path,
+                format: match raw_options.quote_file_format.as_str() {
+                    "fortune" => QuoteFileFormat::Fortune,
+                    "csv" => QuoteFileFormat::Csv,
+                    "json" => QuoteFileFormat::Json,
+                    "kindle" => QuoteFileFormat::Kindle,
+                    _ => QuoteFileFormat::Auto,
+                },
+                order: match raw_options.quote_order.as_str() {
+                    "sequential" => QuoteOrder::Sequential,
+                    "no-repeat" => QuoteOrder::NoRepeat(raw_options.quote_no_repeat),
+                    _ => QuoteOrder::Random,
+                },
+            };
+        } else if let Some(path) = raw_options.todo {
+            text = TextOptions::Todo(TodoOptions {
+                path,
+                priority: raw_options.todo_priority,
+                context: raw_options.todo_context,
+                due_within: raw_options.todo_due_within,
+                hide_done: raw_options.todo_hide_done,
+                limit: raw_options.todo_limit,
+            });
+        } else if raw_options.clock {
+            let formats = if raw_options.clock_format.is_empty() {
+                vec!["%H:%M".to_string(), "%A %-d %B %Y".to_string()]
+            } else {
+                raw_options.clock_format
+            };
+            text = TextOptions::Clock {
+                formats,
+                time_zone: raw_options.time_zone,
+            };
+        } else if let Some(dictionary) = raw_options.word_of_the_day {
+            text = TextOptions::WordOfTheDay(dictionary);
         } else if raw_options.random_quote {
             text = TextOptions::RandomQuote;
+        } else if raw_options.network_quote {
+            text = TextOptions::NetworkQuote;
         } else if raw_options.random_word {
             text = TextOptions::RandomWord;
         }
@@ -77,10 +554,127 @@ impl Options {
             font.color = font_color;
         }
 
+        let display = DisplayOptions {
+            panorama: raw_options.panorama,
+            bezel_mm: raw_options.bezel,
+        };
+
+        let location = match (raw_options.latitude, raw_options.longitude) {
+            (Some(latitude), Some(longitude)) => Some(Location {
+                latitude,
+                longitude,
+            }),
+            _ => None,
+        };
+
+        let fallback = FallbackOptions {
+            background: match raw_options.fallback_background.as_str() {
+                "none" => None,
+                "gradient" => Some(BackgroundOptions::Gradient),
+                color => Some(BackgroundOptions::Color(color.to_string())),
+            },
+            text: match raw_options.fallback_text.as_str() {
+                "random-word" => Some(TextOptions::RandomWord),
+                "none" => None,
+                _ => Some(TextOptions::RandomQuote),
+            },
+        };
+
+        let words = WordOptions {
+            language: raw_options.word_lang,
+            syllables: raw_options.word_syllables,
+            count: raw_options.word_count,
+            case: match raw_options.word_case.as_str() {
+                "lower" => WordCase::Lower,
+                "upper" => WordCase::Upper,
+                "capitalize" => WordCase::Capitalize,
+                "title" => WordCase::Title,
+                _ => WordCase::Unchanged,
+            },
+            real_words: raw_options.real_words,
+        };
+
+        let calendar = raw_options.calendar.then(|| CalendarOptions {
+            ics: raw_options.calendar_ics,
+            week_start: match raw_options.week_start.as_str() {
+                "sunday" => WeekStart::Sunday,
+                _ => WeekStart::Monday,
+            },
+            upcoming_events: raw_options.calendar_events,
+            position: Anchor::from_name(&raw_options.calendar_position),
+        });
+
+        let periods: Vec<ProgressPeriod> = raw_options
+            .progress
+            .iter()
+            .map(|period| match period.as_str() {
+                "year" => ProgressPeriod::Year,
+                "quarter" => ProgressPeriod::Quarter,
+                "month" => ProgressPeriod::Month,
+                _ => ProgressPeriod::Day,
+            })
+            .collect();
+        let progress =
+            (!raw_options.countdown.is_empty() || !periods.is_empty()).then(|| ProgressOptions {
+                countdowns: raw_options.countdown,
+                periods,
+                position: Anchor::from_name(&raw_options.progress_position),
+            });
+
+        let format = raw_options
+            .format
+            .clone()
+            .or_else(|| output_extension(raw_options.output.as_deref()));
+        let to_stdout = raw_options.output.as_deref() == Some(Path::new("-"));
+        let output = OutputOptions {
+            path: raw_options.output,
+            format: format
+                .as_deref()
+                .and_then(OutputFormat::from_name)
+                .unwrap_or(OutputFormat::Png),
+            quality: raw_options.quality,
+            compression: match raw_options.compression.as_str() {
+                "best" => PngCompression::Best,
+                "default" => PngCompression::Default,
+                _ => PngCompression::Fast,
+            },
+            set: !raw_options.no_set && !to_stdout,
+            set_command: raw_options.set_command,
+        };
+        let palette =
+            (raw_options.palette || raw_options.palette_dir.is_some()).then_some(PaletteOptions {
+                directory: raw_options.palette_dir,
+            });
+        let history = RetentionOptions {
+            keep: raw_options.history_keep,
+            max_size_mb: raw_options.history_max_size,
+            max_age: raw_options.history_max_age,
+        };
+
+        let quotes = QuoteOptions {
+            tag: raw_options.quote_tag,
+            author: raw_options.quote_author,
+            max_length: raw_options.max_quote_length,
+        };
+
         Self {
             background,
             text,
             font,
+            display,
+            location,
+            fallback,
+            words,
+            quotes,
+            calendar,
+            progress,
+            shapes: raw_options.shape,
+            overlays: raw_options.overlay,
+            offline: raw_options.offline,
+            output,
+            history,
+            post_hooks: raw_options.post_hook,
+            palette,
         }
     }
 }
@@ -90,6 +684,20 @@ pub enum BackgroundOptions {
     Url(String),
     RandomImage,
     Color(String), // "random" / "red", "green", "teal" / "rgb(20, 48, 200)" / "#FE7789"
+    /// Gradient following the color of the sky through the day
+    Sky,
+    /// Gradient between two random colors
+    Gradient,
+    /// SVG file covering the background, or repeated as tiles `tile_width` points wide
+    Svg {
+        path: PathBuf,
+        tile_width: Option<f32>,
+    },
+    /// Solid color taken from the image of another background, "dominant" or "accent"
+    ImageColor {
+        source: Box<BackgroundOptions>,
+        color: String,
+    },
 }
 
 /// Contains the options for the raw text to be displayed. Does
@@ -97,8 +705,316 @@ pub enum BackgroundOptions {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum TextOptions {
     Message(String),
+    /// Contents of a file
+    MessageFile(PathBuf),
+    /// Output of a shell command
+    MessageCommand {
+        command: String,
+        timeout: Duration,
+    },
+    /// Random quote among the bundled and cached quotes
+    #[serde(alias = "OfflineQuote")]
     RandomQuote,
+    /// Random quote fetched from the network
+    NetworkQuote,
     RandomWord,
+    /// Items of a todo list, drawn as a checklist
+    Todo(TodoOptions),
+    /// Current date and time, one line per strftime format
+    Clock {
+        formats: Vec<String>,
+        /// IANA name of the time zone, the local time zone if [None]
+        time_zone: Option<String>,
+    },
+    /// Word of the day and its definition, from a dictionary file
+    WordOfTheDay(PathBuf),
+    /// Quote read from a file
+    QuoteFile {
+        path: PathBuf,
+        format: QuoteFileFormat,
+        order: QuoteOrder,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum QuoteFileFormat {
+    /// Detected from the extension and contents of the file
+    Auto,
+    /// BSD fortune file
+    Fortune,
+    Csv,
+    Json,
+    /// Highlights of a Kindle "My Clippings.txt" file
+    Kindle,
+}
+
+/// Order quotes are selected from a quote file in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum QuoteOrder {
+    Random,
+    /// Every quote in turn, continuing from the previous selection
+    Sequential,
+    /// Random, without repeating any of the given number of recent quotes
+    NoRepeat(usize),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WordOptions {
+    /// "fr" or the path of a list of words, one per line
+    pub language: String,
+    /// Number of syllables of every word, any if [None]
+    pub syllables: Option<usize>,
+    /// Number of words
+    pub count: usize,
+    pub case: WordCase,
+    /// Pick words from the language's list instead of generating them
+    pub real_words: bool,
+}
+
+impl Default for WordOptions {
+    fn default() -> Self {
+        Self {
+            language: "fr".to_string(),
+            syllables: None,
+            count: 1,
+            case: WordCase::Unchanged,
+            real_words: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum WordCase {
+    Unchanged,
+    Lower,
+    Upper,
+    /// Uppercase first letter of the first word
+    Capitalize,
+    /// Uppercase first letter of every word
+    Title,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TodoOptions {
+    /// todo.txt or Markdown file
+    pub path: PathBuf,
+    /// Lowest priority displayed, items without a priority are hidden if set
+    pub priority: Option<char>,
+    pub context: Option<String>,
+    /// Only items due within this many days are displayed if set
+    pub due_within: Option<u32>,
+    pub hide_done: bool,
+    /// Most items displayed
+    pub limit: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CalendarOptions {
+    /// iCalendar files of the events marked on the calendar
+    pub ics: Vec<PathBuf>,
+    pub week_start: WeekStart,
+    /// Number of upcoming events listed beside the calendar
+    pub upcoming_events: usize,
+    pub position: Anchor,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum WeekStart {
+    Monday,
+    Sunday,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProgressOptions {
+    /// Countdowns, drawn above the progress bars
+    pub countdowns: Vec<Countdown>,
+    /// Periods drawn as progress bars, in order
+    pub periods: Vec<ProgressPeriod>,
+    pub position: Anchor,
+}
+
+/// Date the days are counted until, described by its label.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Countdown {
+    pub date: CountdownDate,
+    /// Defaults to the date
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum CountdownDate {
+    Once(NaiveDate),
+    /// Next occurrence of a day of the year, such as a birthday
+    Yearly {
+        month: u32,
+        day: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum ProgressPeriod {
+    Year,
+    Quarter,
+    Month,
+    Day,
+}
+
+/// Position of an element on the primary display.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum Anchor {
+    #[serde(alias = "center")]
+    Center,
+    #[serde(alias = "top-left")]
+    TopLeft,
+    #[serde(alias = "top-right")]
+    TopRight,
+    #[serde(alias = "bottom-left")]
+    BottomLeft,
+    #[serde(alias = "bottom-right")]
+    BottomRight,
+}
+
+impl Anchor {
+    /// Parses one of [ANCHORS], defaulting to [Anchor::Center].
+    fn from_name(name: &str) -> Self {
+        match name {
+            "top-left" => Anchor::TopLeft,
+            "top-right" => Anchor::TopRight,
+            "bottom-left" => Anchor::BottomLeft,
+            "bottom-right" => Anchor::BottomRight,
+            _ => Anchor::Center,
+        }
+    }
+}
+
+/// Filters restricting the quotes that can be displayed. [None] allows any quote.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct QuoteOptions {
+    pub tag: Option<String>,
+    /// Part of the author's name, case insensitive
+    pub author: Option<String>,
+    /// Maximum number of characters of the quote
+    pub max_length: Option<usize>,
+}
+
+/// Where and how the wallpaper is saved, and whether it is set.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutputOptions {
+    /// File the wallpaper is written to, "-" for stdout. A new file in the
+    /// application's data directory if [None]
+    pub path: Option<PathBuf>,
+    pub format: OutputFormat,
+    /// Quality of lossy formats, from 1 to 100
+    pub quality: u8,
+    pub compression: PngCompression,
+    /// Set the saved wallpaper as the wallpaper
+    pub set: bool,
+    /// Shell command setting the wallpaper, detected from the desktop if [None]
+    #[serde(default)]
+    pub set_command: Option<String>,
+}
+
+impl OutputOptions {
+    pub fn to_stdout(&self) -> bool {
+        self.path.as_deref() == Some(Path::new("-"))
+    }
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            path: None,
+            format: OutputFormat::Png,
+            quality: 90,
+            compression: PngCompression::Fast,
+            set: true,
+            set_command: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Bmp,
+    Tiff,
+}
+
+impl OutputFormat {
+    /// Format of a --format value or of an --output extension.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "png" => Some(OutputFormat::Png),
+            "jpeg" | "jpg" => Some(OutputFormat::Jpeg),
+            "webp" => Some(OutputFormat::Webp),
+            "bmp" => Some(OutputFormat::Bmp),
+            "tiff" | "tif" => Some(OutputFormat::Tiff),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Bmp => "bmp",
+            OutputFormat::Tiff => "tiff",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PaletteOptions {
+    /// Directory of the palette files, "palette" in the data directory if [None]
+    pub directory: Option<PathBuf>,
+}
+
+/// Limits of the wallpaper history. The oldest wallpapers are removed from the
+/// history, and their files deleted, once any limit is exceeded.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RetentionOptions {
+    /// Number of wallpapers kept, unlimited if 0
+    pub keep: usize,
+    /// Total size of the kept wallpapers in megabytes
+    pub max_size_mb: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+impl Default for RetentionOptions {
+    fn default() -> Self {
+        Self {
+            keep: 100,
+            max_size_mb: None,
+            max_age: None,
+        }
+    }
+}
+
+/// Replacements used when the background or text cannot be created, e.g. when
+/// the network is unavailable. [None] fails instead.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FallbackOptions {
+    pub background: Option<BackgroundOptions>,
+    pub text: Option<TextOptions>,
+}
+
+impl Default for FallbackOptions {
+    fn default() -> Self {
+        Self {
+            background: Some(BackgroundOptions::Gradient),
+            text: Some(TextOptions::RandomQuote),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -106,6 +1022,55 @@ pub struct FontOptions {
     /// Relative path to the font
     pub font_path: Option<String>,
     pub color: String,
-    /// Size of the font in pixels
-    pub font_size: Option<u32>,
+    /// Size of the font
+    pub font_size: Option<FontSize>,
+}
+
+/// Size of a font, either in physical pixels or in points.
+///
+/// A point is one logical pixel: it is the same physical size on every display
+/// and covers `scale_factor` physical pixels on HiDPI displays.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum FontSize {
+    Pixels(f32),
+    Points(f32),
+}
+
+impl FontSize {
+    /// Size of the font in physical pixels on a display with the given scale factor.
+    pub fn to_pixels(self, scale_factor: f32) -> f32 {
+        match self {
+            FontSize::Pixels(pixels) => pixels,
+            FontSize::Points(points) => points * scale_factor,
+        }
+    }
+}
+
+impl FromStr for FontSize {
+    type Err = String;
+
+    fn from_str(raw_size: &str) -> Result<Self, Self::Err> {
+        let raw_size = raw_size.trim();
+        let (value, size): (&str, fn(f32) -> FontSize) =
+            if let Some(points) = raw_size.strip_suffix("pt") {
+                (points, FontSize::Points)
+            } else if let Some(pixels) = raw_size.strip_suffix("px") {
+                (pixels, FontSize::Pixels)
+            } else {
+                (raw_size, FontSize::Pixels)
+            };
+
+        match value.trim().parse::<f32>() {
+            Ok(value) if value > 0.0 => Ok(size(value)),
+            _ => Err(format!("Invalid text size {raw_size}")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DisplayOptions {
+    /// Span a single background across every display
+    pub panorama: bool,
+    /// Width of the bezels between displays in millimeters
+    pub bezel_mm: f32,
 }
@@ -0,0 +1,740 @@
+use std::path::Path;
+
+use chrono::{
+    DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use glyph_brush_layout::{BuiltInLineBreaker, HorizontalAlign, Layout, VerticalAlign};
+use image::Rgba;
+
+use crate::args::{Anchor, CalendarOptions, WeekStart};
+use crate::display::Region;
+use crate::error::{Error, Result};
+use crate::text::{draw_textbox, TextBox, TextConfig, TextSize};
+use crate::BackgroundImage;
+
+/// Most occurrences of a recurring event that are computed.
+const MAX_OCCURRENCES: usize = 100_000;
+/// Width of the list of upcoming events, in calendar cells.
+const LIST_CELLS: u32 = 8;
+/// Longest summary of an upcoming event, in characters.
+const MAX_SUMMARY_LENGTH: usize = 24;
+/// Furthest upcoming events are searched for.
+const UPCOMING_DAYS: i64 = 366;
+
+/// Event of an iCalendar file.
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// Start of the event in local time, midnight for all day events
+    pub start: NaiveDateTime,
+    pub all_day: bool,
+    pub summary: String,
+    recurrence: Option<Recurrence>,
+    /// Starts of the occurrences removed by EXDATE or moved by RECURRENCE-ID,
+    /// and whether they are dates
+    exceptions: Vec<(NaiveDateTime, bool)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// Subset of an iCalendar RRULE: the frequency, interval and end of the
+/// recurrence, and the days of weekly recurrences. BYMONTHDAY, BYSETPOS, BYDAY
+/// of other frequencies and other BY rules are ignored.
+#[derive(Debug, Clone)]
+struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<NaiveDate>,
+    /// Days of the week of a weekly recurrence (BYDAY), Monday first. The day
+    /// of the start if empty.
+    weekdays: Vec<Weekday>,
+}
+
+impl Event {
+    /// Starts of the occurrences of the event between two dates, inclusive.
+    pub fn occurrences(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDateTime> {
+        let Some(recurrence) = &self.recurrence else {
+            let date = self.start.date();
+            return if from <= date && date <= to && !self.is_exception(&self.start) {
+                vec![self.start]
+            } else {
+                vec![]
+            };
+        };
+
+        let limit = recurrence
+            .count
+            .unwrap_or(MAX_OCCURRENCES)
+            .min(MAX_OCCURRENCES);
+        let mut count = 0;
+        let mut occurrences = vec![];
+        for n in 0..MAX_OCCURRENCES {
+            let step = n as u32 * recurrence.interval;
+            let period_start = match recurrence.frequency {
+                Frequency::Daily => self.start.checked_add_signed(Duration::days(step as i64)),
+                Frequency::Weekly => self.start.checked_add_signed(Duration::weeks(step as i64)),
+                Frequency::Monthly => self.start.checked_add_months(Months::new(step)),
+                Frequency::Yearly => self.start.checked_add_months(Months::new(step * 12)),
+            };
+            let Some(period_start) = period_start else {
+                break;
+            };
+            let starts = match recurrence.frequency {
+                // The days of the week of the start's week, not before the start
+                Frequency::Weekly if !recurrence.weekdays.is_empty() => {
+                    let monday = period_start
+                        - Duration::days(period_start.weekday().num_days_from_monday() as i64);
+                    recurrence
+                        .weekdays
+                        .iter()
+                        .map(|weekday| {
+                            monday + Duration::days(weekday.num_days_from_monday() as i64)
+                        })
+                        .filter(|start| *start >= self.start)
+                        .collect()
+                }
+                _ => vec![period_start],
+            };
+
+            for start in starts {
+                // Removed occurrences still count toward COUNT
+                if count >= limit
+                    || start.date() > to
+                    || recurrence.until.is_some_and(|until| start.date() > until)
+                {
+                    return occurrences;
+                }
+                count += 1;
+                if start.date() >= from && !self.is_exception(&start) {
+                    occurrences.push(start);
+                }
+            }
+        }
+
+        occurrences
+    }
+
+    fn is_exception(&self, start: &NaiveDateTime) -> bool {
+        self.exceptions.iter().any(|(exception, is_date)| {
+            if *is_date {
+                exception.date() == start.date()
+            } else {
+                exception == start
+            }
+        })
+    }
+}
+
+/// Reads the events of an iCalendar (.ics) file.
+pub fn read_events(path: &Path) -> Result<Vec<Event>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("Failed to read calendar {}. {e}", path.display())))?;
+
+    Ok(parse_ics(&contents))
+}
+
+/// Properties of the event being parsed.
+#[derive(Default)]
+struct PartialEvent {
+    uid: Option<String>,
+    start: Option<(NaiveDateTime, bool)>,
+    summary: String,
+    recurrence: Option<Recurrence>,
+    exceptions: Vec<(NaiveDateTime, bool)>,
+    /// Occurrence of a recurring event with the same UID that this event replaces
+    recurrence_id: Option<(NaiveDateTime, bool)>,
+    cancelled: bool,
+}
+
+fn parse_ics(contents: &str) -> Vec<Event> {
+    // Long lines are folded onto lines starting with a space or a tab
+    let mut lines: Vec<String> = vec![];
+    for line in contents.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(previous)) => previous.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut parsed: Vec<PartialEvent> = vec![];
+    let mut event: Option<PartialEvent> = None;
+    for line in lines {
+        let Some((name_and_parameters, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut parameters = name_and_parameters.split(';');
+        let name = parameters.next().unwrap_or_default().to_uppercase();
+        let parameters: Vec<&str> = parameters.collect();
+
+        match (name.as_str(), &mut event) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                event = Some(PartialEvent::default())
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                parsed.extend(event.take());
+            }
+            ("UID", Some(event)) => event.uid = Some(value.trim().to_string()),
+            ("DTSTART", Some(event)) => event.start = parse_date_time(value, &parameters),
+            ("SUMMARY", Some(event)) => event.summary = unescape(value),
+            ("RRULE", Some(event)) => event.recurrence = parse_recurrence(value),
+            ("EXDATE", Some(event)) => event.exceptions.extend(
+                value
+                    .split(',')
+                    .filter_map(|value| parse_date_time(value, &parameters)),
+            ),
+            ("RECURRENCE-ID", Some(event)) => {
+                event.recurrence_id = parse_date_time(value, &parameters)
+            }
+            ("STATUS", Some(event)) => event.cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+            _ => {}
+        }
+    }
+
+    // Occurrences replaced by another event are removed from their recurring event
+    let replaced: Vec<(Option<String>, (NaiveDateTime, bool))> = parsed
+        .iter()
+        .filter_map(|event| Some((event.uid.clone(), event.recurrence_id?)))
+        .collect();
+    for (uid, occurrence) in replaced {
+        for event in &mut parsed {
+            if event.uid == uid && event.recurrence_id.is_none() {
+                event.exceptions.push(occurrence);
+            }
+        }
+    }
+
+    parsed
+        .into_iter()
+        .filter(|event| !event.cancelled)
+        .filter_map(|event| {
+            let (start, all_day) = event.start?;
+            Some(Event {
+                start,
+                all_day,
+                summary: event.summary,
+                recurrence: event.recurrence,
+                exceptions: event.exceptions,
+            })
+        })
+        .collect()
+}
+
+/// Parses a DATE or DATE-TIME value into local time. Returns whether it is a date.
+fn parse_date_time(value: &str, parameters: &[&str]) -> Option<(NaiveDateTime, bool)> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Some((date.and_hms_opt(0, 0, 0)?, true));
+    }
+
+    let local = |time: DateTime<Utc>| time.with_timezone(&Local).naive_local();
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((local(Utc.from_utc_datetime(&time)), false));
+    }
+
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let time_zone = parameters
+        .iter()
+        .find_map(|parameter| parameter.strip_prefix("TZID="))
+        .and_then(|name| name.trim_matches('"').parse::<Tz>().ok());
+    match time_zone {
+        Some(time_zone) => {
+            let time = time_zone.from_local_datetime(&time).earliest()?;
+            Some((local(time.with_timezone(&Utc)), false))
+        }
+        // Floating times are in the local time zone
+        None => Some((time, false)),
+    }
+}
+
+fn parse_recurrence(value: &str) -> Option<Recurrence> {
+    let mut recurrence = Recurrence {
+        frequency: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        weekdays: vec![],
+    };
+    let mut frequency = None;
+    for part in value.split(';') {
+        let (name, value) = part.split_once('=')?;
+        match name.to_uppercase().as_str() {
+            "FREQ" => {
+                frequency = match value.to_uppercase().as_str() {
+                    "DAILY" => Some(Frequency::Daily),
+                    "WEEKLY" => Some(Frequency::Weekly),
+                    "MONTHLY" => Some(Frequency::Monthly),
+                    "YEARLY" => Some(Frequency::Yearly),
+                    _ => None,
+                }
+            }
+            "INTERVAL" => recurrence.interval = value.parse().ok().filter(|i| *i > 0)?,
+            "COUNT" => recurrence.count = value.parse().ok(),
+            "BYDAY" => {
+                // Days with an ordinal, such as "2MO" in monthly rules, are not supported
+                recurrence.weekdays = value
+                    .split(',')
+                    .filter_map(|day| match day.trim().to_uppercase().as_str() {
+                        "MO" => Some(Weekday::Mon),
+                        "TU" => Some(Weekday::Tue),
+                        "WE" => Some(Weekday::Wed),
+                        "TH" => Some(Weekday::Thu),
+                        "FR" => Some(Weekday::Fri),
+                        "SA" => Some(Weekday::Sat),
+                        "SU" => Some(Weekday::Sun),
+                        _ => None,
+                    })
+                    .collect();
+                recurrence
+                    .weekdays
+                    .sort_by_key(|weekday| weekday.num_days_from_monday());
+                recurrence.weekdays.dedup();
+            }
+            "UNTIL" => {
+                recurrence.until = value
+                    .get(..8)
+                    .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+            }
+            _ => {}
+        }
+    }
+
+    recurrence.frequency = frequency?;
+    Some(recurrence)
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+/// Occurrences of the events between two dates, in chronological order.
+pub fn occurrences_between(
+    events: &[Event],
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Vec<(NaiveDateTime, &Event)> {
+    let mut occurrences: Vec<(NaiveDateTime, &Event)> = events
+        .iter()
+        .flat_map(|event| {
+            event
+                .occurrences(from, to)
+                .into_iter()
+                .map(move |start| (start, event))
+        })
+        .collect();
+    occurrences.sort_by_key(|(start, _)| *start);
+
+    occurrences
+}
+
+/// Draws the month of `now` as a grid, anchored in `region`, with the days
+/// that have events marked and optionally the upcoming events listed beside it.
+pub fn draw_calendar(
+    image: &mut BackgroundImage,
+    region: &Region,
+    options: &CalendarOptions,
+    now: NaiveDateTime,
+    events: &[Event],
+    style: &TextConfig,
+) {
+    let today = now.date();
+    let cell = (region.height / 22).max(8);
+    let grid_width = 7 * cell;
+    let grid_height = 8 * cell;
+    let gap = cell / 2;
+    let list_width = if options.upcoming_events > 0 {
+        LIST_CELLS * cell
+    } else {
+        0
+    };
+    let total_width = grid_width + if list_width > 0 { gap + list_width } else { 0 };
+
+    let (left, top) = anchor_position(region, options.position, total_width, grid_height);
+    // The list goes on the side of the grid facing the center of the region
+    let list_on_left = matches!(options.position, Anchor::TopRight | Anchor::BottomRight);
+    let (grid_left, list_left) = if list_on_left {
+        (left + list_width + gap, left)
+    } else {
+        (left, left + grid_width + gap)
+    };
+
+    let color = style.color;
+    let with_alpha = |alpha: u8| Rgba([color.0[0], color.0[1], color.0[2], alpha]);
+    let text = |image: &mut BackgroundImage, text: String, size: f32, x: u32, y: u32| {
+        let textbox = TextBox {
+            text,
+            width: cell * 7,
+            height: cell,
+            style: TextConfig {
+                size: TextSize::PxScale(size),
+                ..style.clone()
+            },
+        };
+        draw_textbox(image, textbox, x, y);
+    };
+
+    // Month and year
+    let first = today.with_day(1).unwrap_or(today);
+    text(
+        image,
+        first.format("%B %Y").to_string(),
+        cell as f32 * 0.55,
+        grid_left + grid_width / 2,
+        top + cell / 2,
+    );
+
+    let week_start = match options.week_start {
+        WeekStart::Monday => Weekday::Mon,
+        WeekStart::Sunday => Weekday::Sun,
+    };
+    let offset =
+        (first.weekday().num_days_from_monday() + 7 - week_start.num_days_from_monday()) % 7;
+    let last = first
+        .checked_add_months(Months::new(1))
+        .and_then(|next| next.pred_opt())
+        .unwrap_or(first);
+    let weeks = (offset + last.day()).div_ceil(7);
+
+    // Days of the week, with the weekend shaded
+    let mut weekday = week_start;
+    for column in 0..7 {
+        let x = grid_left + column * cell;
+        if matches!(weekday, Weekday::Sat | Weekday::Sun) {
+            image.fill_rect(
+                &Region {
+                    x,
+                    y: top + cell,
+                    width: cell,
+                    height: (weeks + 1) * cell,
+                },
+                &with_alpha(24),
+            );
+        }
+        let name: String = weekday.to_string().chars().take(2).collect();
+        text(
+            image,
+            name,
+            cell as f32 * 0.35,
+            x + cell / 2,
+            top + cell + cell / 2,
+        );
+        weekday = weekday.succ();
+    }
+
+    // Days of the month
+    let event_days: Vec<NaiveDate> = occurrences_between(events, first, last)
+        .into_iter()
+        .map(|(start, _)| start.date())
+        .collect();
+
+    for day in 1..=last.day() {
+        let index = offset + day - 1;
+        let x = grid_left + (index % 7) * cell;
+        let y = top + 2 * cell + (index / 7) * cell;
+
+        if day == today.day() {
+            let inset = cell / 10;
+            image.fill_rect(
+                &Region {
+                    x: x + inset,
+                    y: y + inset,
+                    width: cell - 2 * inset,
+                    height: cell - 2 * inset,
+                },
+                &with_alpha(72),
+            );
+        }
+        text(
+            image,
+            day.to_string(),
+            cell as f32 * 0.4,
+            x + cell / 2,
+            y + cell / 2,
+        );
+
+        if event_days.iter().any(|date| date.day() == day) {
+            let size = (cell / 12).max(2);
+            image.fill_rect(
+                &Region {
+                    x: x + cell / 2 - size / 2,
+                    y: y + cell * 4 / 5,
+                    width: size,
+                    height: size,
+                },
+                &color,
+            );
+        }
+    }
+
+    if options.upcoming_events > 0 {
+        draw_upcoming_events(
+            image,
+            events,
+            now,
+            options.upcoming_events,
+            style,
+            list_left,
+            top,
+            cell,
+        );
+    }
+}
+
+/// Next `count` occurrences of the events within `UPCOMING_DAYS` of `now`.
+/// Events that already started today are left out, unless they last all day.
+fn upcoming_events(
+    events: &[Event],
+    now: NaiveDateTime,
+    count: usize,
+) -> Vec<(NaiveDateTime, &Event)> {
+    let today = now.date();
+    occurrences_between(events, today, today + Duration::days(UPCOMING_DAYS))
+        .into_iter()
+        .filter(|(start, event)| event.all_day || *start >= now)
+        .take(count)
+        .collect()
+}
+
+/// Lists the next events, one per line, starting at (left, top).
+#[allow(clippy::too_many_arguments)]
+fn draw_upcoming_events(
+    image: &mut BackgroundImage,
+    events: &[Event],
+    now: NaiveDateTime,
+    count: usize,
+    style: &TextConfig,
+    left: u32,
+    top: u32,
+    cell: u32,
+) {
+    let upcoming = upcoming_events(events, now, count);
+
+    let left_aligned = TextConfig {
+        size: TextSize::PxScale(cell as f32 * 0.32),
+        layout: Layout::Wrap {
+            h_align: HorizontalAlign::Left,
+            v_align: VerticalAlign::Center,
+            line_breaker: BuiltInLineBreaker::UnicodeLineBreaker,
+        },
+        ..style.clone()
+    };
+    for (line, (start, event)) in upcoming.into_iter().enumerate() {
+        let when = if event.all_day {
+            start.format("%a %-d %b").to_string()
+        } else {
+            start.format("%a %-d %b %H:%M").to_string()
+        };
+        let mut summary: String = event.summary.chars().take(MAX_SUMMARY_LENGTH).collect();
+        if event.summary.chars().count() > MAX_SUMMARY_LENGTH {
+            summary.push_str("...");
+        }
+        let textbox = TextBox {
+            text: format!("{when}  {summary}"),
+            width: LIST_CELLS * cell,
+            height: cell,
+            style: left_aligned.clone(),
+        };
+        draw_textbox(
+            image,
+            textbox,
+            left,
+            top + cell / 2 + line as u32 * cell * 3 / 5,
+        );
+    }
+}
+
+/// Top left corner of a `width` by `height` block anchored in a region, with a
+/// margin from the region's edges.
+pub fn anchor_position(region: &Region, anchor: Anchor, width: u32, height: u32) -> (u32, u32) {
+    let margin = region.height / 20;
+    let left = region.x + margin;
+    let right = (region.x + region.width).saturating_sub(margin + width);
+    let top = region.y + margin;
+    let bottom = (region.y + region.height).saturating_sub(margin + height);
+
+    match anchor {
+        Anchor::TopLeft => (left, top),
+        Anchor::TopRight => (right, top),
+        Anchor::BottomLeft => (left, bottom),
+        Anchor::BottomRight => (right, bottom),
+        Anchor::Center => (
+            (region.x + region.width / 2).saturating_sub(width / 2),
+            (region.y + region.height / 2).saturating_sub(height / 2),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn event(properties: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\n{properties}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+        )
+    }
+
+    #[test]
+    fn parses_events() {
+        let events = parse_ics(&event(
+            "DTSTART:20240305T093000\r\nSUMMARY:Stand-up\\, daily\r\n  with notes",
+        ));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].start, time(2024, 3, 5, 9, 30));
+        assert!(!events[0].all_day);
+        assert_eq!(events[0].summary, "Stand-up, daily with notes");
+
+        let events = parse_ics(&event("DTSTART;VALUE=DATE:20240229\r\nSUMMARY:Leap day"));
+        assert_eq!(events[0].start, time(2024, 2, 29, 0, 0));
+        assert!(events[0].all_day);
+
+        // Events without a start are skipped, properties outside events ignored
+        let events = parse_ics("SUMMARY:Outside\r\nBEGIN:VEVENT\r\nSUMMARY:No start\r\nEND:VEVENT");
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn unescapes_text() {
+        assert_eq!(unescape(r"a\nb\, c\; d\\e"), "a b, c; d\\e");
+    }
+
+    #[test]
+    fn recurrences() {
+        let occurrences = |properties: &str, from: NaiveDate, to: NaiveDate| {
+            parse_ics(&event(properties))[0].occurrences(from, to)
+        };
+
+        assert_eq!(
+            occurrences(
+                "DTSTART:20240101T080000\r\nRRULE:FREQ=DAILY;INTERVAL=2;COUNT=3",
+                date(2024, 1, 1),
+                date(2024, 12, 31),
+            ),
+            [
+                time(2024, 1, 1, 8, 0),
+                time(2024, 1, 3, 8, 0),
+                time(2024, 1, 5, 8, 0),
+            ]
+        );
+        assert_eq!(
+            occurrences(
+                "DTSTART;VALUE=DATE:20240131\r\nRRULE:FREQ=MONTHLY;UNTIL=20240401",
+                date(2024, 1, 1),
+                date(2024, 12, 31),
+            ),
+            [
+                time(2024, 1, 31, 0, 0),
+                time(2024, 2, 29, 0, 0),
+                time(2024, 3, 31, 0, 0),
+            ]
+        );
+        // Occurrences before the range count toward COUNT
+        assert_eq!(
+            occurrences(
+                "DTSTART:20240101T080000\r\nRRULE:FREQ=WEEKLY;COUNT=3",
+                date(2024, 1, 10),
+                date(2024, 12, 31),
+            ),
+            [time(2024, 1, 15, 8, 0)]
+        );
+    }
+
+    #[test]
+    fn weekly_recurrences_on_several_days() {
+        // Wednesday start, every other week on Monday, Wednesday and Friday
+        let events = parse_ics(&event(
+            "DTSTART:20240103T180000\r\nRRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=FR,MO,WE;COUNT=5",
+        ));
+        assert_eq!(
+            events[0].occurrences(date(2024, 1, 1), date(2024, 12, 31)),
+            [
+                time(2024, 1, 3, 18, 0),
+                time(2024, 1, 5, 18, 0),
+                time(2024, 1, 15, 18, 0),
+                time(2024, 1, 17, 18, 0),
+                time(2024, 1, 19, 18, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn excluded_and_moved_occurrences() {
+        let events = parse_ics(
+            "BEGIN:VEVENT\r\n\
+             UID:meeting\r\n\
+             DTSTART:20240101T100000\r\n\
+             RRULE:FREQ=DAILY;COUNT=5\r\n\
+             EXDATE:20240102T100000,20240103T100000\r\n\
+             SUMMARY:Meeting\r\n\
+             END:VEVENT\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:meeting\r\n\
+             RECURRENCE-ID:20240104T100000\r\n\
+             DTSTART:20240104T150000\r\n\
+             SUMMARY:Meeting\r\n\
+             END:VEVENT\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:meeting\r\n\
+             RECURRENCE-ID:20240105T100000\r\n\
+             DTSTART:20240105T100000\r\n\
+             STATUS:CANCELLED\r\n\
+             END:VEVENT\r\n",
+        );
+        let starts: Vec<NaiveDateTime> =
+            occurrences_between(&events, date(2024, 1, 1), date(2024, 1, 31))
+                .into_iter()
+                .map(|(start, _)| start)
+                .collect();
+        assert_eq!(starts, [time(2024, 1, 1, 10, 0), time(2024, 1, 4, 15, 0)]);
+
+        // All day exceptions remove the whole day
+        let events = parse_ics(&event(
+            "DTSTART;VALUE=DATE:20240101\r\nRRULE:FREQ=DAILY;COUNT=3\r\nEXDATE;VALUE=DATE:20240102",
+        ));
+        assert_eq!(
+            events[0].occurrences(date(2024, 1, 1), date(2024, 1, 31)),
+            [time(2024, 1, 1, 0, 0), time(2024, 1, 3, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn upcoming_events_leave_out_started_events() {
+        let events = parse_ics(
+            "BEGIN:VEVENT\r\nDTSTART:20240610T090000\r\nSUMMARY:Started\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20240610\r\nSUMMARY:All day\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nDTSTART:20240610T140000\r\nSUMMARY:Later\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nDTSTART:20240612T080000\r\nSUMMARY:Next\r\nEND:VEVENT\r\n",
+        );
+        let summaries = |count| -> Vec<String> {
+            upcoming_events(&events, time(2024, 6, 10, 12, 0), count)
+                .into_iter()
+                .map(|(_, event)| event.summary.clone())
+                .collect()
+        };
+        assert_eq!(summaries(5), ["All day", "Later", "Next"]);
+        assert_eq!(summaries(2), ["All day", "Later"]);
+    }
+}
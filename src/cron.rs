@@ -0,0 +1,414 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+/// Furthest a cron expression is searched for a matching minute, long enough
+/// to find a 29th of February.
+pub const SEARCH_LIMIT: Duration = Duration::days(8 * 366);
+
+/// A standard five field cron expression: "<minute> <hour> <day of month> <month> <day of week>".
+///
+/// Each field accepts "*", values, ranges ("1-5"), lists ("1,15") and steps
+/// ("*/15", "0-30/10"). Months and days of the week also accept their English
+/// abbreviations ("jan", "mon"). Sunday is both 0 and 7.
+#[derive(Debug, Clone)]
+pub struct CronExpression {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days_of_month: Vec<bool>,
+    months: Vec<bool>,
+    days_of_week: Vec<bool>,
+    /// Whether the day of the month and day of the week fields were restricted.
+    /// As in cron, a day matches if it matches either restricted field.
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+impl CronExpression {
+    /// Whether the expression matches the minute containing `time`.
+    #[cfg(test)]
+    fn matches(&self, time: &NaiveDateTime) -> bool {
+        self.matches_day(&time.date())
+            && self.minutes[time.minute() as usize]
+            && self.hours[time.hour() as usize]
+    }
+
+    /// Whether the expression matches some minute of `date`.
+    fn matches_day(&self, date: &NaiveDate) -> bool {
+        let day_of_month = self.days_of_month[date.day() as usize];
+        let day_of_week = self.days_of_week[date.weekday().num_days_from_sunday() as usize];
+        let day = match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+
+        day && self.months[date.month() as usize]
+    }
+
+    /// First matching minute of a day at or after `from` (hour, minute).
+    fn first_time_from(&self, (from_hour, from_minute): (u32, u32)) -> Option<NaiveTime> {
+        (from_hour..24)
+            .filter(|hour| self.hours[*hour as usize])
+            .find_map(|hour| {
+                let start = if hour == from_hour { from_minute } else { 0 };
+                (start..60)
+                    .find(|minute| self.minutes[*minute as usize])
+                    .and_then(|minute| NaiveTime::from_hms_opt(hour, minute, 0))
+            })
+    }
+
+    /// Last matching minute of a day at or before `until` (hour, minute).
+    fn last_time_until(&self, (until_hour, until_minute): (u32, u32)) -> Option<NaiveTime> {
+        (0..=until_hour)
+            .rev()
+            .filter(|hour| self.hours[*hour as usize])
+            .find_map(|hour| {
+                let end = if hour == until_hour { until_minute } else { 59 };
+                (0..=end)
+                    .rev()
+                    .find(|minute| self.minutes[*minute as usize])
+                    .and_then(|minute| NaiveTime::from_hms_opt(hour, minute, 0))
+            })
+    }
+
+    /// First matching minute strictly after `time`.
+    pub fn next_after(&self, time: &NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = truncate_to_minute(time) + Duration::minutes(1);
+        let limit = (*time + SEARCH_LIMIT).date();
+        let mut date = start.date();
+        let mut from = (start.hour(), start.minute());
+        while date <= limit {
+            if !self.months[date.month() as usize] {
+                // Skip to the first day of the next month
+                date = first_day_of_next_month(&date)?;
+                from = (0, 0);
+                continue;
+            }
+            if self.matches_day(&date) {
+                if let Some(time) = self.first_time_from(from) {
+                    return Some(date.and_time(time));
+                }
+            }
+            date = date.succ_opt()?;
+            from = (0, 0);
+        }
+
+        None
+    }
+
+    /// Last matching minute at or before `time`, looking back at most `lookback`.
+    pub fn previous_at_or_before(
+        &self,
+        time: &NaiveDateTime,
+        lookback: Duration,
+    ) -> Option<NaiveDateTime> {
+        let limit = *time - lookback;
+        let mut date = time.date();
+        let mut until = (time.hour(), time.minute());
+        while date >= limit.date() {
+            if !self.months[date.month() as usize] {
+                // Skip to the last day of the previous month
+                date = date.with_day(1)?.pred_opt()?;
+                until = (23, 59);
+                continue;
+            }
+            if self.matches_day(&date) {
+                if let Some(time) = self.last_time_until(until) {
+                    let candidate = date.and_time(time);
+                    return (candidate >= limit).then_some(candidate);
+                }
+            }
+            date = date.pred_opt()?;
+            until = (23, 59);
+        }
+
+        None
+    }
+}
+
+impl FromStr for CronExpression {
+    type Err = String;
+
+    fn from_str(raw_expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = raw_expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "Invalid cron expression \"{raw_expression}\", expected 5 fields"
+            ));
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7, &DAY_NAMES)?;
+        // Sunday is both 0 and 7
+        days_of_week[0] |= days_of_week[7];
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59, &[])?,
+            hours: parse_field(fields[1], 0, 23, &[])?,
+            days_of_month: parse_field(fields[2], 1, 31, &[])?,
+            months: parse_field(fields[3], 1, 12, &MONTH_NAMES)?,
+            days_of_week,
+            // As in Vixie cron, "*/2" leaves the field unrestricted
+            day_of_month_restricted: !fields[2].starts_with('*'),
+            day_of_week_restricted: !fields[4].starts_with('*'),
+        })
+    }
+}
+
+fn first_day_of_next_month(date: &NaiveDate) -> Option<NaiveDate> {
+    match date.month() {
+        12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+        month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1),
+    }
+}
+
+fn truncate_to_minute(time: &NaiveDateTime) -> NaiveDateTime {
+    time.date()
+        .and_hms_opt(time.hour(), time.minute(), 0)
+        .unwrap()
+}
+
+/// Parses a cron field into a lookup table indexed by value.
+///
+/// `names` are alternative names for the values, starting at `min`.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<Vec<bool>, String> {
+    let invalid = || format!("Invalid cron field \"{field}\"");
+    let parse_value = |raw_value: &str| -> Result<u32, String> {
+        let lowercase = raw_value.to_lowercase();
+        let value = match names.iter().position(|name| *name == lowercase) {
+            Some(index) => index as u32 + min,
+            None => raw_value.parse().map_err(|_| invalid())?,
+        };
+        if value < min || value > max {
+            return Err(invalid());
+        }
+        Ok(value)
+    };
+
+    let mut table = vec![false; max as usize + 1];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start)?, parse_value(end)?)
+        } else {
+            let value = parse_value(range)?;
+            // "5/10" means every 10 starting at 5
+            if part.contains('/') {
+                (value, max)
+            } else {
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(invalid());
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            table[value as usize] = true;
+        }
+    }
+
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn cron(raw_expression: &str) -> CronExpression {
+        CronExpression::from_str(raw_expression).unwrap()
+    }
+
+    #[test]
+    fn parses_valid_expressions() {
+        for raw_expression in [
+            "* * * * *",
+            "*/15 9-17 * * mon-fri",
+            "0,30 8 1,15 jan,JUL 0",
+            "5/10 * * * 7",
+            "0-30/10 23 31 12 sun",
+        ] {
+            assert!(
+                CronExpression::from_str(raw_expression).is_ok(),
+                "{raw_expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for raw_expression in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * 32 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "30-10 * * * *",
+            "* * * foo *",
+            "a * * * *",
+        ] {
+            assert!(
+                CronExpression::from_str(raw_expression).is_err(),
+                "{raw_expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn sunday_is_zero_and_seven() {
+        // 2024-06-02 is a Sunday
+        let sunday = time(2024, 6, 2, 12, 0);
+        assert!(cron("0 12 * * 0").matches(&sunday));
+        assert!(cron("0 12 * * 7").matches(&sunday));
+        assert!(cron("0 12 * * sun").matches(&sunday));
+        assert!(!cron("0 12 * * 1-6").matches(&sunday));
+    }
+
+    #[test]
+    fn next_after_is_strictly_after() {
+        let expression = cron("30 9 * * *");
+        assert_eq!(
+            expression.next_after(&time(2024, 6, 3, 9, 30)),
+            Some(time(2024, 6, 4, 9, 30))
+        );
+        assert_eq!(
+            expression.next_after(&time(2024, 6, 3, 9, 29)),
+            Some(time(2024, 6, 3, 9, 30))
+        );
+    }
+
+    #[test]
+    fn next_after_with_steps() {
+        let expression = cron("*/15 * * * *");
+        assert_eq!(
+            expression.next_after(&time(2024, 6, 3, 9, 31)),
+            Some(time(2024, 6, 3, 9, 45))
+        );
+        assert_eq!(
+            expression.next_after(&time(2024, 6, 3, 23, 50)),
+            Some(time(2024, 6, 4, 0, 0))
+        );
+    }
+
+    #[test]
+    fn next_after_without_match() {
+        assert_eq!(cron("0 0 30 2 *").next_after(&time(2024, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn previous_at_or_before_includes_time() {
+        let expression = cron("0 8 * * *");
+        assert_eq!(
+            expression.previous_at_or_before(&time(2024, 6, 3, 8, 0), Duration::days(1)),
+            Some(time(2024, 6, 3, 8, 0))
+        );
+        assert_eq!(
+            expression.previous_at_or_before(&time(2024, 6, 3, 7, 59), Duration::days(1)),
+            Some(time(2024, 6, 2, 8, 0))
+        );
+        assert_eq!(
+            expression.previous_at_or_before(&time(2024, 6, 3, 7, 59), Duration::hours(12)),
+            None
+        );
+    }
+
+    #[test]
+    fn restricted_days_match_either_field() {
+        // The 15th of the month or any Monday; 2024-06-03 is a Monday
+        let expression = cron("0 12 15 * mon");
+        assert_eq!(
+            expression.next_after(&time(2024, 6, 1, 0, 0)),
+            Some(time(2024, 6, 3, 12, 0))
+        );
+        assert_eq!(
+            expression.next_after(&time(2024, 6, 10, 13, 0)),
+            Some(time(2024, 6, 15, 12, 0))
+        );
+        assert_eq!(
+            expression.previous_at_or_before(&time(2024, 6, 16, 0, 0), Duration::days(31)),
+            Some(time(2024, 6, 15, 12, 0))
+        );
+    }
+
+    #[test]
+    fn stepped_days_of_month_are_unrestricted() {
+        // "*/2" days of the month and Mondays, which must both match
+        let expression = cron("0 12 */2 * mon");
+        // 2024-06-03 is a Monday on an odd day, 2024-06-17 the next odd Monday
+        assert!(!expression.matches(&time(2024, 6, 5, 12, 0)));
+        assert!(expression.matches(&time(2024, 6, 3, 12, 0)));
+        assert_eq!(
+            expression.next_after(&time(2024, 6, 3, 12, 0)),
+            Some(time(2024, 6, 17, 12, 0))
+        );
+    }
+
+    #[test]
+    fn yearly_expressions() {
+        let expression = cron("0 0 1 jan *");
+        assert_eq!(
+            expression.next_after(&time(2024, 6, 3, 12, 0)),
+            Some(time(2025, 1, 1, 0, 0))
+        );
+        assert_eq!(
+            expression.previous_at_or_before(&time(2024, 12, 31, 23, 59), SEARCH_LIMIT),
+            Some(time(2024, 1, 1, 0, 0))
+        );
+    }
+
+    #[test]
+    fn leap_days() {
+        let expression = cron("30 12 29 feb *");
+        assert_eq!(
+            expression.next_after(&time(2024, 3, 1, 0, 0)),
+            Some(time(2028, 2, 29, 12, 30))
+        );
+        assert_eq!(
+            expression.previous_at_or_before(&time(2027, 6, 1, 0, 0), SEARCH_LIMIT),
+            Some(time(2024, 2, 29, 12, 30))
+        );
+    }
+
+    #[test]
+    fn previous_at_or_before_within_the_day() {
+        let expression = cron("*/20 9-17 * * mon-fri");
+        // 2024-06-08 is a Saturday
+        assert_eq!(
+            expression.previous_at_or_before(&time(2024, 6, 8, 10, 0), Duration::days(7)),
+            Some(time(2024, 6, 7, 17, 40))
+        );
+        assert_eq!(
+            expression.previous_at_or_before(&time(2024, 6, 7, 10, 39), Duration::days(7)),
+            Some(time(2024, 6, 7, 10, 20))
+        );
+        assert_eq!(
+            expression.previous_at_or_before(&time(2024, 6, 7, 8, 59), Duration::days(7)),
+            Some(time(2024, 6, 6, 17, 40))
+        );
+    }
+}
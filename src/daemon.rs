@@ -0,0 +1,176 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+#[cfg(unix)]
+use signal_hook::consts::SIGHUP;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::flag;
+
+use crate::args::{DaemonOptions, Options};
+use crate::error::Result;
+use crate::layout::load_layout;
+use crate::{draw_wallpaper, render_background, BackgroundLayer};
+
+/// Delay before retrying a failed wallpaper, doubled after every consecutive failure.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// How often the daemon checks for signals while it waits.
+const TICK: Duration = Duration::from_millis(250);
+
+/// Tracks the termination (SIGTERM, SIGINT) and reload (SIGHUP) signals
+/// received by the process.
+pub struct Signals {
+    terminate: Arc<AtomicBool>,
+    reload: Arc<AtomicBool>,
+}
+
+impl Signals {
+    pub fn register() -> Result<Self> {
+        let terminate = Arc::new(AtomicBool::new(false));
+        let reload = Arc::new(AtomicBool::new(false));
+        flag::register(SIGTERM, Arc::clone(&terminate))?;
+        flag::register(SIGINT, Arc::clone(&terminate))?;
+        #[cfg(unix)]
+        flag::register(SIGHUP, Arc::clone(&reload))?;
+
+        Ok(Self { terminate, reload })
+    }
+
+    pub fn terminated(&self) -> bool {
+        self.terminate.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether a reload was requested since the last call.
+    pub fn take_reload(&self) -> bool {
+        self.reload.swap(false, Ordering::Relaxed)
+    }
+
+    /// Sleeps for `duration`, waking early if a signal is received.
+    pub fn wait(&self, duration: Duration) {
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            if self.terminated() || self.reload.load(Ordering::Relaxed) {
+                return;
+            }
+            std::thread::sleep(TICK.min(duration.saturating_sub(start.elapsed())));
+        }
+    }
+}
+
+/// Regenerates the wallpaper every `daemon_options.every` until SIGTERM or SIGINT
+/// is received. SIGHUP reloads the layout and regenerates the wallpaper immediately.
+///
+/// With `daemon_options.text_every`, the text is redrawn in between on the same
+/// background, which is not recreated.
+pub fn run(daemon_options: DaemonOptions, options: Options) -> Result<()> {
+    let signals = Signals::register()?;
+
+    let load_options = || match &daemon_options.layout {
+        Some(name) => load_layout(name),
+        None => Ok(options.clone()),
+    };
+    let mut options = load_options()?;
+
+    log(&format!(
+        "Started, updating the wallpaper every {}s.",
+        daemon_options.every.as_secs()
+    ));
+
+    let mut failures = 0;
+    // Background layer and when it was rendered
+    let mut layer: Option<(BackgroundLayer, Instant)> = None;
+    while !signals.terminated() {
+        if signals.take_reload() {
+            match load_options() {
+                Ok(reloaded) => {
+                    options = reloaded;
+                    layer = None;
+                    log("Reloaded layout.");
+                }
+                Err(e) => log(&format!(
+                    "Failed to reload layout, keeping the previous one. {e}"
+                )),
+            }
+        }
+
+        let expired = layer
+            .as_ref()
+            .is_none_or(|(_, rendered)| rendered.elapsed() >= daemon_options.every);
+        let redraw = !expired && daemon_options.text_every.is_some();
+        if expired || daemon_options.text_every.is_none() {
+            layer = None;
+        }
+
+        let result = match &mut layer {
+            Some((layer, _)) => draw_wallpaper(&options, layer),
+            None => render_background(&options, None).and_then(|mut rendered| {
+                let path = draw_wallpaper(&options, &mut rendered);
+                layer = Some((rendered, Instant::now()));
+                path
+            }),
+        };
+
+        let delay = match result {
+            Ok(report) => {
+                let path = report.path;
+                failures = 0;
+                // Redraws replace the previous wallpaper of the background in the
+                // history, which deletes its file unless it was rated
+                if !redraw {
+                    log(&format!("Updated wallpaper to {}.", path.display()));
+                }
+                next_update_delay(
+                    &daemon_options,
+                    layer.as_ref().map(|(_, rendered)| rendered),
+                )
+            }
+            Err(e) => {
+                failures += 1;
+                layer = None;
+                let delay = retry_delay(failures, daemon_options.every);
+                log(&format!(
+                    "Failed to update wallpaper, retrying in {}s. {e}",
+                    delay.as_secs()
+                ));
+                delay
+            }
+        };
+
+        signals.wait(delay);
+    }
+
+    log("Stopped.");
+    Ok(())
+}
+
+/// Time until the next redraw of the text or, without `text_every`, until the
+/// next wallpaper.
+fn next_update_delay(daemon_options: &DaemonOptions, rendered: Option<&Instant>) -> Duration {
+    let background_delay = rendered
+        .map(|rendered| daemon_options.every.saturating_sub(rendered.elapsed()))
+        .unwrap_or(daemon_options.every);
+
+    match daemon_options.text_every {
+        Some(text_every) => {
+            // Align redraws on the wall clock, e.g. to the start of each minute
+            let now = Local::now();
+            let millis = now.timestamp() as u128 * 1000 + now.timestamp_subsec_millis() as u128;
+            let period = text_every.as_millis().max(1);
+            let text_delay = Duration::from_millis((period - millis % period) as u64);
+            text_delay.min(background_delay)
+        }
+        None => background_delay,
+    }
+}
+
+/// Exponential backoff after `failures` consecutive failures, never longer
+/// than `max_delay`.
+pub fn retry_delay(failures: u32, max_delay: Duration) -> Duration {
+    let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+    INITIAL_RETRY_DELAY.saturating_mul(factor).min(max_delay)
+}
+
+pub fn log(message: &str) {
+    println!("[{}] {message}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// Word and its meaning, as found in a dictionary.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DictionaryEntry {
+    pub word: String,
+    #[serde(default, alias = "pos")]
+    pub part_of_speech: Option<String>,
+    #[serde(default)]
+    pub pronunciation: Option<String>,
+    #[serde(default, alias = "meaning")]
+    pub definition: String,
+}
+
+/// JSON dictionaries are either a list of entries or an object mapping words
+/// to their definition.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonDictionary {
+    Entries(Vec<DictionaryEntry>),
+    Definitions(BTreeMap<String, String>),
+}
+
+/// Reads the entries of a dictionary file.
+///
+/// Accepts JSON files (see [JsonDictionary]), WordNet database files ("data.noun",
+/// "data.verb", ...) and tab separated files with the columns
+/// "<word>\t<part of speech>\t<pronunciation>\t<definition>".
+pub fn read_dictionary(path: &Path) -> Result<Vec<DictionaryEntry>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("Failed to read dictionary {}. {e}", path.display())))?;
+
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+    let entries: Vec<DictionaryEntry> = if is_json {
+        let dictionary = serde_json::from_str(&contents)
+            .map_err(|e| Error::Config(format!("Invalid dictionary {}. {e}", path.display())))?;
+        match dictionary {
+            JsonDictionary::Entries(entries) => entries,
+            JsonDictionary::Definitions(definitions) => definitions
+                .into_iter()
+                .map(|(word, definition)| DictionaryEntry {
+                    word,
+                    part_of_speech: None,
+                    pronunciation: None,
+                    definition,
+                })
+                .collect(),
+        }
+    } else {
+        let synsets: Vec<DictionaryEntry> =
+            contents.lines().filter_map(parse_wordnet_line).collect();
+        if synsets.is_empty() {
+            contents.lines().filter_map(parse_tsv_line).collect()
+        } else {
+            synsets
+        }
+    };
+
+    let entries: Vec<DictionaryEntry> = entries
+        .into_iter()
+        .filter(|entry| !entry.word.trim().is_empty())
+        .collect();
+    if entries.is_empty() {
+        return Err(Error::Config(format!(
+            "No words found in dictionary {}",
+            path.display()
+        )));
+    }
+    Ok(entries)
+}
+
+/// Parses a synset of a WordNet database file:
+/// "<offset> <lex file> <type> <word count> <word> <lex id> ... | <gloss>".
+/// The license header, whose lines start with spaces, is skipped.
+fn parse_wordnet_line(line: &str) -> Option<DictionaryEntry> {
+    let (data, gloss) = line.split_once(" | ")?;
+    let fields: Vec<&str> = data.split_whitespace().collect();
+    let is_offset = |field: &str| field.len() == 8 && field.chars().all(|c| c.is_ascii_digit());
+    if fields.len() < 5 || !is_offset(fields[0]) {
+        return None;
+    }
+
+    let part_of_speech = match fields[2] {
+        "n" => "noun",
+        "v" => "verb",
+        "a" | "s" => "adjective",
+        "r" => "adverb",
+        _ => return None,
+    };
+    // The gloss holds the definition followed by quoted examples, separated by ";"
+    let definition = gloss
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+
+    Some(DictionaryEntry {
+        word: fields[4].replace('_', " "),
+        part_of_speech: Some(part_of_speech.to_string()),
+        pronunciation: None,
+        definition,
+    })
+}
+
+fn parse_tsv_line(line: &str) -> Option<DictionaryEntry> {
+    let mut columns = line.split('\t').map(str::trim);
+    let non_empty = |column: Option<&str>| column.filter(|c| !c.is_empty()).map(String::from);
+
+    let word = non_empty(columns.next())?;
+    Some(DictionaryEntry {
+        word,
+        part_of_speech: non_empty(columns.next()),
+        pronunciation: non_empty(columns.next()),
+        definition: columns.next().unwrap_or_default().to_string(),
+    })
+}
+
+/// Entry of the day. Every date maps to the same entry as long as the dictionary
+/// does not change, and consecutive dates map to unrelated entries.
+pub fn entry_of_the_day(entries: &[DictionaryEntry], date: NaiveDate) -> Option<&DictionaryEntry> {
+    if entries.is_empty() {
+        return None;
+    }
+
+    // SplitMix64 of the day, spreading neighbouring days across the dictionary
+    let mut hash = (date.num_days_from_ce() as u64).wrapping_add(0x9e3779b97f4a7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^= hash >> 31;
+
+    entries.get((hash % entries.len() as u64) as usize)
+}
@@ -0,0 +1,236 @@
+use display_info::DisplayInfo;
+
+use crate::error::{Error, Result};
+
+/// Pixel density of a display with a scale factor of 1. [`DisplayInfo`] does
+/// not report the physical size of a display, so millimeter measurements are
+/// converted to pixels using this approximation.
+const BASE_DPI: f32 = 96.0;
+const MM_PER_INCH: f32 = 25.4;
+
+/// Rectangular region of an image, in pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    /// Center of the region.
+    pub fn center(&self) -> (u32, u32) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+}
+
+/// Returns all of the connected displays.
+pub fn all_displays() -> Result<Vec<DisplayInfo>> {
+    DisplayInfo::all().ok_or(Error::DisplayDetection)
+}
+
+/// Returns the primary display, if one is connected.
+pub fn primary_display(displays: &[DisplayInfo]) -> Option<&DisplayInfo> {
+    displays.iter().find(|display| display.is_primary)
+}
+
+/// Ratio of physical pixels to logical pixels on a display.
+pub fn scale_factor(display: &DisplayInfo) -> f32 {
+    display.scale_factor.max(1.0)
+}
+
+/// Computes the dimensions of a display in physical pixels.
+///
+/// [`DisplayInfo`] reports dimensions in logical pixels, so on HiDPI (Retina)
+/// displays the native resolution is larger by the display's scale factor.
+pub fn physical_resolution(display: &DisplayInfo) -> (u32, u32) {
+    let scale = scale_factor(display);
+
+    (
+        (display.width as f32 * scale).round() as u32,
+        (display.height as f32 * scale).round() as u32,
+    )
+}
+
+/// A single canvas that spans every connected display.
+///
+/// Displays keep their relative positions from the desktop arrangement. Each
+/// gap between neighbouring displays is widened by the bezel width so that
+/// lines crossing from one display to the next stay straight.
+///
+/// The canvas is rendered at the largest scale factor of all the displays so
+/// that it is crisp on every display, HiDPI or not.
+#[derive(Debug, Clone)]
+pub struct Panorama {
+    pub width: u32,
+    pub height: u32,
+    /// Region of the canvas shown on each display, in the same order as the
+    /// displays the panorama was created from.
+    pub regions: Vec<Region>,
+    /// Index of the primary display in `regions`.
+    pub primary: usize,
+    /// Number of canvas pixels per logical pixel.
+    pub scale_factor: f32,
+}
+
+impl Panorama {
+    pub fn new(displays: &[DisplayInfo], bezel_mm: f32) -> Option<Self> {
+        let min_x = displays.iter().map(|display| display.x).min()?;
+        let min_y = displays.iter().map(|display| display.y).min()?;
+        let primary = displays
+            .iter()
+            .position(|display| display.is_primary)
+            .unwrap_or(0);
+
+        let scale = displays.iter().map(scale_factor).fold(1.0, f32::max);
+        let to_pixels = |logical: f32| (logical * scale).round() as u32;
+
+        let dpi = BASE_DPI * scale;
+        let bezel = (bezel_mm.max(0.0) / MM_PER_INCH * dpi).round() as u32;
+
+        // A bezel separates displays touching side by side (one above the other):
+        // everything right of (below) such a seam moves by the bezel
+        let columns = seams(displays, |display| {
+            (display.x, display.width, display.y, display.height)
+        });
+        let rows = seams(displays, |display| {
+            (display.y, display.height, display.x, display.width)
+        });
+
+        let regions: Vec<Region> = displays
+            .iter()
+            .map(|display| {
+                let column = columns.iter().filter(|x| **x <= display.x).count() as u32;
+                let row = rows.iter().filter(|y| **y <= display.y).count() as u32;
+
+                Region {
+                    x: to_pixels((display.x - min_x) as f32) + column * bezel,
+                    y: to_pixels((display.y - min_y) as f32) + row * bezel,
+                    width: to_pixels(display.width as f32),
+                    height: to_pixels(display.height as f32),
+                }
+            })
+            .collect();
+
+        let width = regions.iter().map(|region| region.x + region.width).max()?;
+        let height = regions
+            .iter()
+            .map(|region| region.y + region.height)
+            .max()?;
+
+        Some(Self {
+            width,
+            height,
+            regions,
+            primary,
+            scale_factor: scale,
+        })
+    }
+
+    /// Region of the canvas shown on the primary display.
+    pub fn primary_region(&self) -> Region {
+        self.regions[self.primary]
+    }
+}
+
+/// Positions where a display ends and another one starts along an axis, with
+/// both displays overlapping on the other axis. `span` gives the start and
+/// length of a display along the axis, then along the other axis.
+fn seams(
+    displays: &[DisplayInfo],
+    span: impl Fn(&DisplayInfo) -> (i32, u32, i32, u32),
+) -> Vec<i32> {
+    let mut seams: Vec<i32> = vec![];
+    for first in displays {
+        let (start, length, cross_start, cross_length) = span(first);
+        let end = start + length as i32;
+        let touches = displays.iter().any(|second| {
+            let (second_start, _, second_cross_start, second_cross_length) = span(second);
+            second_start == end
+                && second_cross_start < cross_start + cross_length as i32
+                && cross_start < second_cross_start + second_cross_length as i32
+        });
+        if touches && !seams.contains(&end) {
+            seams.push(end);
+        }
+    }
+
+    seams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(x: i32, y: i32, width: u32, height: u32) -> DisplayInfo {
+        DisplayInfo {
+            id: 0,
+            x,
+            y,
+            width,
+            height,
+            rotation: 0.0,
+            scale_factor: 1.0,
+            is_primary: x == 0 && y == 0,
+        }
+    }
+
+    /// 1mm at 96 DPI, rounded to 4 pixels
+    const BEZEL_MM: f32 = 1.0;
+    const BEZEL: u32 = 4;
+
+    fn origins(panorama: &Panorama) -> Vec<(u32, u32)> {
+        panorama
+            .regions
+            .iter()
+            .map(|region| (region.x, region.y))
+            .collect()
+    }
+
+    #[test]
+    fn side_by_side_displays_are_separated_by_a_bezel() {
+        let displays = [display(0, 0, 1920, 1080), display(1920, 0, 1920, 1080)];
+        let panorama = Panorama::new(&displays, BEZEL_MM).unwrap();
+
+        assert_eq!(origins(&panorama), [(0, 0), (1920 + BEZEL, 0)]);
+        assert_eq!((panorama.width, panorama.height), (3840 + BEZEL, 1080));
+    }
+
+    #[test]
+    fn stacked_displays_with_offsets_have_no_horizontal_bezel() {
+        let displays = [display(0, 0, 1920, 1080), display(500, 1080, 1280, 1024)];
+        let panorama = Panorama::new(&displays, BEZEL_MM).unwrap();
+
+        assert_eq!(origins(&panorama), [(0, 0), (500, 1080 + BEZEL)]);
+    }
+
+    #[test]
+    fn displays_apart_have_no_bezel() {
+        // Level on the x axis but not touching vertically
+        let displays = [display(0, 0, 1920, 1080), display(1920, 2000, 1920, 1080)];
+        let panorama = Panorama::new(&displays, BEZEL_MM).unwrap();
+
+        assert_eq!(origins(&panorama), [(0, 0), (1920, 2000)]);
+    }
+
+    #[test]
+    fn grid_of_displays() {
+        let displays = [
+            display(0, 0, 1920, 1080),
+            display(1920, 0, 1920, 1080),
+            display(0, 1080, 1920, 1080),
+            display(1920, 1080, 1920, 1080),
+        ];
+        let panorama = Panorama::new(&displays, BEZEL_MM).unwrap();
+
+        assert_eq!(
+            origins(&panorama),
+            [
+                (0, 0),
+                (1920 + BEZEL, 0),
+                (0, 1080 + BEZEL),
+                (1920 + BEZEL, 1080 + BEZEL)
+            ]
+        );
+    }
+}
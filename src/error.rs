@@ -0,0 +1,105 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors that can occur while generating or setting a wallpaper.
+#[derive(Debug)]
+pub enum Error {
+    /// A layout, schedule or option is invalid
+    Config(String),
+    /// A request to a remote service failed
+    Network(String),
+    /// A required API key is not set, holds the name of its environment variable
+    MissingApiKey(&'static str),
+    /// A color could not be parsed
+    InvalidColor(String),
+    /// A font could not be read or parsed
+    FontLoad(String),
+    /// An image could not be decoded or encoded
+    Decode(image::ImageError),
+    /// Reading or writing a file failed
+    Io(std::io::Error),
+    /// The connected displays could not be detected
+    DisplayDetection,
+    /// The wallpaper could not be applied
+    Wallpaper(String),
+    /// An external command failed or timed out
+    Command(String),
+}
+
+impl Error {
+    /// Exit code of the process when it fails with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Config(_) => 2,
+            Error::Network(_) => 3,
+            Error::MissingApiKey(_) => 4,
+            Error::InvalidColor(_) => 5,
+            Error::FontLoad(_) => 6,
+            Error::Decode(_) => 7,
+            Error::Io(_) => 8,
+            Error::DisplayDetection => 9,
+            Error::Wallpaper(_) => 10,
+            Error::Command(_) => 11,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(message) => write!(f, "{message}"),
+            Error::Network(message) => write!(f, "Network request failed. {message}"),
+            Error::MissingApiKey(name) => {
+                write!(f, "Missing the {name} environment variable")
+            }
+            Error::InvalidColor(color) => write!(f, "Invalid color {color}"),
+            Error::FontLoad(message) => write!(f, "Failed to load font. {message}"),
+            Error::Decode(e) => write!(f, "Failed to process image. {e}"),
+            Error::Io(e) => write!(f, "{e}"),
+            Error::DisplayDetection => write!(f, "Failed to detect the connected displays"),
+            Error::Wallpaper(message) => write!(f, "Failed to set wallpaper. {message}"),
+            Error::Command(message) => write!(f, "Command failed. {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Decode(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Network(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Config(e.to_string())
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Config(message)
+    }
+}
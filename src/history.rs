@@ -0,0 +1,349 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::args::{BackgroundOptions, Options, RetentionOptions};
+use crate::error::{Error, Result};
+use crate::preferences::{Choices, Rating};
+use crate::utils::application_data_path;
+
+/// Wallpaper saved by a previous run.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Local>,
+    pub path: PathBuf,
+    /// FNV-1a hash of the file, telling apart wallpapers saved to the same path
+    pub hash: String,
+    /// Options the wallpaper was generated with
+    pub options: Options,
+    /// Random choices made for the wallpaper
+    #[serde(default)]
+    pub choices: Choices,
+    #[serde(default)]
+    pub rating: Option<Rating>,
+    /// Whether the wallpaper was set on the desktop, rather than only saved. Only
+    /// set wallpapers are rated and reverted to.
+    #[serde(default = "default_set")]
+    pub set: bool,
+}
+
+fn default_set() -> bool {
+    true
+}
+
+/// State of the file of an entry, compared to the wallpaper it was saved as.
+enum FileState {
+    Unchanged,
+    /// Replaced by another wallpaper, e.g. one written to the same --output
+    Overwritten,
+    Deleted,
+}
+
+impl HistoryEntry {
+    fn file_state(&self) -> FileState {
+        match std::fs::read(&self.path) {
+            Ok(bytes) if hash(&bytes) == self.hash => FileState::Unchanged,
+            Ok(_) => FileState::Overwritten,
+            Err(_) => FileState::Deleted,
+        }
+    }
+}
+
+/// Path of the history index.
+fn history_path() -> Result<PathBuf> {
+    let mut path = application_data_path()?;
+    path.push("history.json");
+
+    Ok(path)
+}
+
+/// Directory of the wallpapers generated without an output path. Only the files
+/// in it are deleted when the history is pruned.
+pub fn backgrounds_path() -> Result<PathBuf> {
+    let mut path = application_data_path()?;
+    path.push("backgrounds");
+
+    Ok(path)
+}
+
+/// Entries of the history, oldest first.
+pub fn load_history() -> Result<Vec<HistoryEntry>> {
+    let path = history_path()?;
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_history(entries: &[HistoryEntry]) -> Result<()> {
+    std::fs::write(history_path()?, serde_json::to_string_pretty(entries)?)?;
+
+    Ok(())
+}
+
+/// 64-bit FNV-1a hash, as hexadecimal.
+fn hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// Adds a saved wallpaper to the history, `set` if it was set on the desktop, then
+/// prunes the history following the retention options. The entry recorded at
+/// `replacing`, such as the previous redraw of the same background, is removed
+/// unless it was rated. Returns the time of the new entry.
+pub fn record(
+    path: &Path,
+    options: &Options,
+    choices: &Choices,
+    set: bool,
+    replacing: Option<DateTime<Local>>,
+) -> Result<DateTime<Local>> {
+    let bytes = std::fs::read(path)?;
+    let mut entries = load_history().unwrap_or_else(|e| {
+        eprintln!("{e}. Starting a new wallpaper history.");
+        vec![]
+    });
+    let (replaced, mut entries): (Vec<_>, Vec<_>) = entries
+        .drain(..)
+        .partition(|entry| Some(entry.timestamp) == replacing && entry.rating.is_none());
+    let timestamp = Local::now();
+    entries.push(HistoryEntry {
+        timestamp,
+        path: path.to_path_buf(),
+        hash: hash(&bytes),
+        options: options.clone(),
+        choices: choices.clone(),
+        rating: None,
+        set,
+    });
+
+    let removed = prune(&mut entries, &options.history, timestamp);
+    save_history(&entries)?;
+    // Disliked wallpapers stay in the history to steer the random choices, without their file
+    let disliked = entries
+        .iter()
+        .filter(|entry| entry.rating == Some(Rating::Dislike));
+    delete_unreferenced(replaced.iter().chain(&removed).chain(disliked), &entries)?;
+
+    Ok(timestamp)
+}
+
+/// Removes the entries outside of the retention options, oldest first, and
+/// returns them. The newest entry is always kept, and entries whose file was
+/// deleted (such as replaced clock redraws) are removed. Rated entries are
+/// never removed and don't count toward the limits.
+fn prune(
+    entries: &mut Vec<HistoryEntry>,
+    retention: &RetentionOptions,
+    now: DateTime<Local>,
+) -> Vec<HistoryEntry> {
+    let newest = entries.pop();
+    let (rated, unrated): (Vec<_>, Vec<_>) =
+        entries.drain(..).partition(|entry| entry.rating.is_some());
+    let (mut kept, mut removed): (Vec<_>, Vec<_>) =
+        unrated.into_iter().partition(|entry| entry.path.exists());
+    kept.extend(newest);
+
+    if let Some(max_age) = retention.max_age {
+        let max_age = chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX);
+        while kept.len() > 1 && now - kept[0].timestamp > max_age {
+            removed.push(kept.remove(0));
+        }
+    }
+    if retention.keep > 0 {
+        while kept.len() > retention.keep {
+            removed.push(kept.remove(0));
+        }
+    }
+    if let Some(max_size_mb) = retention.max_size_mb {
+        let max_size = max_size_mb * 1024 * 1024;
+        while kept.len() > 1 && total_size(&kept) > max_size {
+            removed.push(kept.remove(0));
+        }
+    }
+
+    kept.extend(rated);
+    kept.sort_by_key(|entry| entry.timestamp);
+    *entries = kept;
+    removed
+}
+
+/// Size in bytes of the distinct files of the entries.
+fn total_size(entries: &[HistoryEntry]) -> u64 {
+    let mut paths: Vec<&Path> = entries.iter().map(|entry| entry.path.as_path()).collect();
+    paths.sort();
+    paths.dedup();
+    paths
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Deletes the generated files of entries that no remaining entry, other than a
+/// disliked one, uses. Files written to an output path chosen by the user are left alone.
+fn delete_unreferenced<'a>(
+    unused: impl Iterator<Item = &'a HistoryEntry>,
+    entries: &[HistoryEntry],
+) -> Result<()> {
+    let backgrounds = backgrounds_path()?;
+    let backgrounds = std::fs::canonicalize(&backgrounds).unwrap_or(backgrounds);
+
+    for entry in unused {
+        let referenced = entries
+            .iter()
+            .any(|kept| kept.rating != Some(Rating::Dislike) && kept.path == entry.path);
+        if referenced || !entry.path.starts_with(&backgrounds) {
+            continue;
+        }
+        std::fs::remove_file(&entry.path).ok();
+        // Displays of a panorama set one by one, see display_panorama_as_background
+        for index in 0.. {
+            let crop_path = entry.path.with_extension(format!("{index}.png"));
+            if std::fs::remove_file(crop_path).is_err() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Entry `steps` set wallpapers before the current one.
+pub fn previous_entry(steps: usize) -> Result<HistoryEntry> {
+    let entries = load_history()?;
+    let set: Vec<&HistoryEntry> = entries.iter().filter(|entry| entry.set).collect();
+    let entry = set.iter().rev().nth(steps).copied().ok_or_else(|| {
+        Error::Config(format!(
+            "No wallpaper {steps} steps back, the history holds {} set wallpapers",
+            set.len()
+        ))
+    })?;
+    match entry.file_state() {
+        FileState::Deleted => Err(Error::Config(format!(
+            "The wallpaper {} was deleted",
+            entry.path.display()
+        ))),
+        FileState::Overwritten => Err(Error::Config(format!(
+            "The wallpaper {} was overwritten since, by a newer wallpaper saved to the same path",
+            entry.path.display()
+        ))),
+        FileState::Unchanged => Ok(entry.clone()),
+    }
+}
+
+/// Prints the newest set wallpapers of the history, numbered by how many steps
+/// back they are, as used by the revert command.
+pub fn print_history(count: usize) -> Result<()> {
+    let entries = load_history()?;
+    let set: Vec<&HistoryEntry> = entries.iter().filter(|entry| entry.set).collect();
+    if set.is_empty() {
+        println!("No wallpapers yet.");
+        return Ok(());
+    }
+
+    for (steps, entry) in set.iter().rev().take(count).enumerate() {
+        let missing = match entry.file_state() {
+            FileState::Unchanged => "",
+            FileState::Overwritten => " (overwritten)",
+            FileState::Deleted => " (deleted)",
+        };
+        let rating = match entry.rating {
+            Some(Rating::Like) => " (liked)",
+            Some(Rating::Dislike) => " (disliked)",
+            None => "",
+        };
+        println!(
+            "{steps:>3}  {}  {}  {}{missing}{rating}",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            &entry.hash[..8.min(entry.hash.len())],
+            entry.path.display(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Rates the newest set wallpaper of the history, which is the current one.
+pub fn rate(rating: Rating) -> Result<HistoryEntry> {
+    let mut entries = load_history()?;
+    let entry = entries
+        .iter_mut()
+        .rev()
+        .find(|entry| entry.set)
+        .ok_or_else(|| Error::Config("No wallpaper to rate yet".to_string()))?;
+    entry.rating = Some(rating);
+    let entry = entry.clone();
+    save_history(&entries)?;
+
+    Ok(entry)
+}
+
+/// Liked entries of the history, oldest first.
+fn favorites() -> Result<Vec<HistoryEntry>> {
+    Ok(load_history()?
+        .into_iter()
+        .filter(|entry| entry.rating == Some(Rating::Like))
+        .collect())
+}
+
+pub fn print_favorites() -> Result<()> {
+    let favorites = favorites()?;
+    if favorites.is_empty() {
+        println!("No favorites yet, add the current wallpaper with \"macground like\".");
+    }
+    for entry in favorites {
+        println!(
+            "{}  {}",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            entry.path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Options generating a wallpaper like a liked one, with its random image and
+/// colors fixed to the ones it was generated with.
+fn favorite_layout(entry: &HistoryEntry) -> Options {
+    let mut options = entry.options.clone();
+    // Colors taken from an image are as random as the image
+    let random_color = matches!(&options.background, BackgroundOptions::Color(color) if color == "random")
+        || matches!(options.background, BackgroundOptions::ImageColor { .. });
+    match &options.background {
+        BackgroundOptions::RandomImage => {
+            if let Some(url) = &entry.choices.image_url {
+                options.background = BackgroundOptions::Url(url.clone());
+            }
+        }
+        _ if random_color => {
+            if let Some([red, green, blue]) = entry.choices.colors.first() {
+                options.background =
+                    BackgroundOptions::Color(format!("#{red:02x}{green:02x}{blue:02x}"));
+            }
+        }
+        _ => {}
+    }
+
+    options
+}
+
+/// Writes the favorites as layout files named after their time to `directory`.
+/// Copied to the layouts directory, they are used with --layout. Returns the
+/// number of exported favorites.
+pub fn export_favorites(directory: &Path) -> Result<usize> {
+    let favorites = favorites()?;
+    std::fs::create_dir_all(directory)?;
+    for entry in &favorites {
+        let name = format!("favorite-{}", entry.timestamp.format("%Y%m%d-%H%M%S"));
+        let path = directory.join(format!("{name}.json"));
+        std::fs::write(path, serde_json::to_string_pretty(&favorite_layout(entry))?)?;
+    }
+
+    Ok(favorites.len())
+}
@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+
+use crate::args::Options;
+use crate::error::Result;
+use crate::utils::application_data_path;
+
+/// Path of the file a layout is saved to.
+fn layout_path(name: &str) -> Result<PathBuf> {
+    let mut path = application_data_path()?;
+    path.push("layouts");
+    path.push(format!("{name}.json"));
+
+    Ok(path)
+}
+
+/// Saves options as a named layout, replacing any existing layout with the same name.
+pub fn save_layout(name: &str, options: &Options) -> Result<()> {
+    let path = layout_path(name)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(options)?)?;
+
+    Ok(())
+}
+
+/// Loads the options saved in a named layout.
+pub fn load_layout(name: &str) -> Result<Options> {
+    let path = layout_path(name)?;
+    let contents = std::fs::read_to_string(&path).map_err(|e| {
+        format!(
+            "Failed to read layout \"{name}\" at {}. {e}",
+            path.display()
+        )
+    })?;
+
+    Ok(serde_json::from_str(&contents)?)
+}
@@ -2,149 +2,752 @@
 use clap::Parser;
 use std::collections::HashMap;
 use std::default::Default;
-use std::error::Error;
-use std::path::PathBuf;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
 // third party
-use image::{ImageBuffer, ImageResult, Pixel, Rgba};
+use chrono::{DateTime, Local, Utc};
+use chrono_tz::Tz;
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngEncoder};
+use image::codecs::tiff::TiffEncoder;
+use image::{
+    ColorType, DynamicImage, ImageBuffer, ImageEncoder, ImageResult, Pixel, Rgba, RgbaImage,
+};
 mod text;
-use display_info::DisplayInfo;
+mod todo;
 use dotenv::dotenv;
 use rand::distributions::Uniform;
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
 use serde::Deserialize;
-use source::{ImageSource, QuoteSource, RandomWordSource, TextSource};
+use source::{
+    ClockSource, CommandSource, ImageSource, MessageFileSource, OfflineQuoteSource,
+    QuoteFileSource, QuoteSource, RandomWordSource, TextSource, TodoSource, WordOfTheDaySource,
+};
 use text::TextConfig;
+use tiny_skia::Pixmap;
 
 mod args;
+mod calendar;
+mod cron;
+mod daemon;
+mod dictionary;
+mod display;
+mod error;
+mod history;
+mod layout;
+mod overlay;
+mod palette;
+mod preferences;
+mod progress;
+mod quote_file;
+mod quotes;
+mod report;
+mod schedule;
+mod setter;
+mod shapes;
 mod source;
+mod sun;
+mod svg;
 mod utils;
 
-use crate::args::{BackgroundOptions, RawOptions, TextOptions};
-use crate::source::{ColorSource, Source};
-use crate::text::{draw_textbox, TextBox, TextSize, FONT_LOADER};
-use crate::utils::application_data_path;
+use crate::args::{
+    BackgroundOptions, Command, OutputFormat, OutputOptions, PngCompression, RawOptions,
+    TextOptions,
+};
+use crate::calendar::{draw_calendar, read_events};
+use crate::display::{
+    all_displays, physical_resolution, primary_display, scale_factor, Panorama, Region,
+};
+use crate::error::{Error, Result};
+use crate::history::{
+    backgrounds_path, export_favorites, previous_entry, print_favorites, print_history, rate,
+    record,
+};
+use crate::layout::{load_layout, save_layout};
+use crate::overlay::draw_overlay;
+use crate::palette::{is_symbolic_color, ImageColors, Palette, SymbolicColors};
+use crate::preferences::{Choices, PhotoCredit, Preferences, Rating};
+use crate::progress::draw_progress;
+use crate::report::{hex, lap, run_hooks, RunReport, Timings};
+use crate::setter::wallpaper_setter;
+use crate::shapes::{render_rounded_rect, render_shapes, unpremultiply, Shape};
+use crate::source::{ColorSource, GradientSource, SkySource, Source, SvgSource};
+use crate::sun::{print_sun_report, sun_position, Location};
+use crate::text::{draw_textbox_stack, TextBox, TextSize, FONT_LOADER};
+use crate::todo::draw_checklist;
+use crate::utils::read_stdin;
 use args::Options;
 
 const WW: u32 = 3840;
 const WH: u32 = 2160;
+/// Size of the lines of text after the first, relative to the first.
+const SECONDARY_TEXT_SCALE: f32 = 0.4;
 
 fn main() {
-    let options = Options::from(RawOptions::parse());
+    let mut raw_options = RawOptions::parse();
+    raw_options.validate();
+    let command = raw_options.command.take();
+    let layout = raw_options.layout.take();
+    let save_layout_name = raw_options.save_layout.take();
+    let json = raw_options.json;
+    let seed = raw_options.seed;
+    if raw_options.message.as_deref() == Some("-") {
+        raw_options.message = Some(read_stdin().unwrap_or_else(|e| exit_with_error(e)));
+    }
 
-    let (width, height) = match get_display_resolution() {
-        Some(dimensions) => dimensions,
-        None => (WW, WH),
+    let options = match layout {
+        Some(name) => load_layout(&name).unwrap_or_else(|e| exit_with_error(e)),
+        None => Options::from(raw_options),
     };
 
-    // Create a background
-    let mut background = match options.background {
-        BackgroundOptions::Color(color) => {
-            let color_source = if &color == "random" {
-                ColorSource::random(width, height)
-            } else {
-                let parsed_color = parse_color(&color);
-                if parsed_color.is_none() {
-                    panic!("Invalid color {}", color)
-                }
-                ColorSource::new(width, height, Rgba(parsed_color.unwrap()))
-            };
-            color_source.get_background()
+    if let Some(name) = save_layout_name {
+        match save_layout(&name, &options) {
+            Ok(()) => println!("Saved layout \"{name}\"."),
+            Err(e) => eprintln!("Failed to save layout \"{name}\". {e}"),
         }
-        BackgroundOptions::RandomImage => {
-            let random_image_url = get_random_image();
-            let image_source = ImageSource::new(width, height, random_image_url);
-            image_source.get_background()
+    }
+
+    if let Some(command) = command {
+        let location = options.location.or_else(Location::from_env);
+        let result = match command {
+            Command::Daemon(daemon_options) => daemon::run(daemon_options, options),
+            Command::Schedule(schedule_options) => {
+                schedule::run(schedule_options, &schedule::SystemClock, location)
+            }
+            Command::Sun => location
+                .map(|location| print_sun_report(&location))
+                .ok_or_else(missing_location),
+            Command::History(history_options) => print_history(history_options.count),
+            Command::Revert(revert_options) => revert(revert_options.steps),
+            Command::Like => rate(Rating::Like).map(|entry| {
+                println!("Added {} to the favorites.", entry.path.display());
+            }),
+            Command::Dislike => rate(Rating::Dislike).map(|entry| {
+                println!("Disliked {}.", entry.path.display());
+            }),
+            Command::Favorites(favorites_options) => match favorites_options.export {
+                Some(directory) => export_favorites(&directory).map(|count| {
+                    println!("Exported {count} favorites to {}.", directory.display());
+                }),
+                None => print_favorites(),
+            },
+        };
+        if let Err(e) = result {
+            exit_with_error(e);
         }
-        BackgroundOptions::Url(url) => {
-            let image_source = ImageSource::new(width, height, url);
-            image_source.get_background()
+        return;
+    }
+
+    match update_wallpaper(&options, seed) {
+        Ok(report) if json => {
+            let report =
+                serde_json::to_string_pretty(&report).unwrap_or_else(|e| exit_with_error(e.into()));
+            // The report goes to stderr when stdout carries the wallpaper itself
+            if options.output.to_stdout() {
+                eprintln!("{report}");
+            } else {
+                println!("{report}");
+            }
         }
+        Ok(_) if options.output.set => println!("Updated wallpaper."),
+        // Nothing else is printed when the wallpaper itself is written to stdout
+        Ok(_) if options.output.to_stdout() => {}
+        Ok(report) => println!("Saved wallpaper to {}.", report.path.display()),
+        Err(e) => exit_with_error(e),
     };
+}
+
+/// Prints the error and exits with its exit code.
+fn exit_with_error(e: Error) -> ! {
+    eprintln!("{e}");
+    process::exit(e.exit_code());
+}
+
+fn missing_location() -> Error {
+    Error::Config(
+        "Missing a location, set --latitude and --longitude or MACGROUND_LATITUDE and MACGROUND_LONGITUDE"
+            .to_string(),
+    )
+}
+
+/// Background of a wallpaper before any text is drawn on it. Kept between runs
+/// to redraw the text (e.g. a clock) without recreating the background.
+#[derive(Debug, Clone)]
+pub struct BackgroundLayer {
+    background: BackgroundImage,
+    panorama: Option<Panorama>,
+    scale_factor: f32,
+    /// Region of the background the text is centered in
+    text_region: Region,
+    /// Random choices made for the background
+    choices: Choices,
+    /// Time of the history entry of the last wallpaper drawn on the background,
+    /// which the next one replaces
+    recorded: Option<DateTime<Local>>,
+    /// Seed of `rng`
+    seed: u64,
+    /// Random generator of every random choice, continued by the text
+    rng: StdRng,
+    /// Time it took to create the background
+    render_time: Duration,
+}
+
+/// Generates a background from the options, saves it to the application's data
+/// directory and sets it as the wallpaper. Returns the report of the run.
+pub fn update_wallpaper(options: &Options, seed: Option<u64>) -> Result<RunReport> {
+    let mut layer = render_background(options, seed)?;
+    draw_wallpaper(options, &mut layer)
+}
+
+/// Creates the background of a wallpaper at the native resolution of the display(s).
+/// Random choices follow `seed`, or a generated seed if [None].
+pub fn render_background(options: &Options, seed: Option<u64>) -> Result<BackgroundLayer> {
+    let displays = all_displays().unwrap_or_else(|e| {
+        eprintln!("{e}, using a {WW}x{WH} background.");
+        vec![]
+    });
+    let panorama = if options.display.panorama {
+        Panorama::new(&displays, options.display.bezel_mm)
+    } else {
+        None
+    };
+
+    // Render at the native resolution of the display(s)
+    let primary = primary_display(&displays);
+    let (width, height) = match &panorama {
+        Some(panorama) => (panorama.width, panorama.height),
+        None => primary.map(physical_resolution).unwrap_or((WW, WH)),
+    };
+    let scale_factor = match &panorama {
+        Some(panorama) => panorama.scale_factor,
+        None => primary.map(scale_factor).unwrap_or(1.0),
+    };
+    let text_region = match &panorama {
+        Some(panorama) => panorama.primary_region(),
+        None => Region {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        },
+    };
+
+    // Create a background
+    let start = Instant::now();
+    let preferences = Preferences::load();
+    let mut choices = Choices::default();
+    let seed = seed.unwrap_or_else(|| thread_rng().gen());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let background = match create_background(
+        &options.background,
+        options,
+        width,
+        height,
+        scale_factor,
+        &preferences,
+        &mut choices,
+        &mut rng,
+    ) {
+        Ok(background) => background,
+        Err(e) => match &options.fallback.background {
+            Some(fallback) => {
+                eprintln!("{e}. Falling back to a {fallback:?} background.");
+                create_background(
+                    fallback,
+                    options,
+                    width,
+                    height,
+                    scale_factor,
+                    &preferences,
+                    &mut choices,
+                    &mut rng,
+                )?
+            }
+            None => return Err(e),
+        },
+    };
+
+    Ok(BackgroundLayer {
+        background,
+        panorama,
+        scale_factor,
+        text_region,
+        choices,
+        recorded: None,
+        seed,
+        rng,
+        render_time: start.elapsed(),
+    })
+}
+
+/// Draws the text onto a copy of the background layer, saves it to the
+/// application's data directory, sets it as the wallpaper and runs the post
+/// hooks. Returns the report of the run.
+pub fn draw_wallpaper(options: &Options, layer: &mut BackgroundLayer) -> Result<RunReport> {
+    let mut start = Instant::now();
+    let mut timings = Timings {
+        background_ms: layer.render_time.as_millis() as u64,
+        ..Default::default()
+    };
+    let mut background = layer.background.clone();
+    let panorama = &layer.panorama;
+    let scale_factor = layer.scale_factor;
+    let (text_width, text_height) = (layer.text_region.width, layer.text_region.height);
+    let (text_x, text_y) = layer.text_region.center();
 
     // Create a message
-    let text = match options.text {
-        TextOptions::Message(message) => {
-            if message.len() > 0 {
-                vec![message]
-            } else {
-                vec![]
+    let preferences = Preferences::load();
+    let rng = &mut layer.rng;
+    let (text_options, text) = match create_text(&options.text, options, &preferences, rng) {
+        Ok(text) => (&options.text, text),
+        Err(e) => match &options.fallback.text {
+            Some(fallback) => {
+                eprintln!("{e}. Falling back to {fallback:?} text.");
+                (fallback, create_text(fallback, options, &preferences, rng)?)
             }
-        }
-        TextOptions::RandomQuote => {
-            let random_quote_source = QuoteSource::default();
-            random_quote_source.source_text()
-        }
-        TextOptions::RandomWord => {
-            let random_word_source = RandomWordSource::default();
-            random_word_source.source_text()
-        }
+            None => return Err(e),
+        },
     };
+    // Quotes are ["<quote>", "<author>"]
+    let is_quote = matches!(
+        text_options,
+        TextOptions::RandomQuote | TextOptions::NetworkQuote | TextOptions::QuoteFile { .. }
+    );
+    let mut choices = layer.choices.clone();
+    if let TextOptions::RandomQuote | TextOptions::NetworkQuote = text_options {
+        choices.quote_author = text.get(1).cloned();
+    }
+    timings.text_ms = lap(&mut start);
+
+    // Symbolic colors come from the background before anything is drawn on it
+    let colors = SymbolicColors::new(&layer.background, &layer.text_region);
+    if let Err(e) =
+        background.draw_shapes(&options.shapes, &layer.text_region, scale_factor, &colors)
+    {
+        eprintln!("{e}. Skipping the shapes.");
+    }
+    for overlay in &options.overlays {
+        if let Err(e) = draw_overlay(&mut background, overlay, &layer.text_region, scale_factor) {
+            eprintln!("{e}. Skipping the overlay.");
+        }
+    }
 
     // Load the required fonts
     let font_path = options.font.font_path.clone();
     if let Some(font_path) = font_path {
-        (*FONT_LOADER).load_font("first".to_string(), PathBuf::from(font_path).as_path());
+        if let Err(e) = (*FONT_LOADER).load_font("first".to_string(), Path::new(&font_path)) {
+            eprintln!("{e}. Falling back to the default font.");
+        }
     }
 
     let text_config = TextConfig {
         size: match options.font.font_size {
-            Some(size) => TextSize::PxScale(size as f32),
+            Some(size) => TextSize::PxScale(size.to_pixels(scale_factor)),
             None => TextSize::FillParent,
         },
-        font: "default".to_string(),
-        color: Rgba(parse_color(&options.font.color).unwrap()),
+        color: Rgba(colors.parse_text_color(&options.font.color)?),
         ..Default::default()
     };
 
-    if text.len() > 0 {
-        let textbox = TextBox {
-            text: text[0].to_owned(),
-            width: width / 2,
-            height: height / 5,
-            style: text_config,
+    if let TextOptions::Todo(_) = text_options {
+        draw_checklist(&mut background, &text, &layer.text_region, &text_config);
+    } else if let Some((first, rest)) = text.split_first() {
+        // Lines after the first (authors, definitions, ...) are drawn smaller, below it
+        let secondary_config = TextConfig {
+            size: match text_config.size {
+                TextSize::PxScale(scale) => TextSize::PxScale(scale * SECONDARY_TEXT_SCALE),
+                TextSize::FillParent => TextSize::FillParent,
+            },
+            ..text_config.clone()
         };
 
-        draw_textbox(&mut background, textbox, width / 2, height / 2);
+        let mut textboxes = vec![TextBox {
+            text: first.to_owned(),
+            width: text_width / 2,
+            height: text_height / 5,
+            style: text_config.clone(),
+        }];
+        textboxes.extend(rest.iter().map(|line| TextBox {
+            text: line.to_owned(),
+            width: text_width / 2,
+            height: text_height / 12,
+            style: secondary_config.clone(),
+        }));
+
+        draw_textbox_stack(&mut background, textboxes, text_height / 60, text_x, text_y);
+    }
+    if let Some(calendar) = &options.calendar {
+        let mut events = vec![];
+        for path in &calendar.ics {
+            match read_events(path) {
+                Ok(calendar_events) => events.extend(calendar_events),
+                Err(e) => eprintln!("{e}. Skipping its events."),
+            }
+        }
+        let style = TextConfig {
+            size: TextSize::FillParent,
+            ..text_config.clone()
+        };
+        draw_calendar(
+            &mut background,
+            &layer.text_region,
+            calendar,
+            Local::now().naive_local(),
+            &events,
+            &style,
+        );
+    }
+    if let Some(progress) = &options.progress {
+        draw_progress(
+            &mut background,
+            &layer.text_region,
+            progress,
+            Local::now().naive_local(),
+            &text_config,
+        );
+    }
+    timings.draw_ms = lap(&mut start);
+
+    let output_path = save_wallpaper(&background, &options.output, &mut layer.rng)?;
+    timings.save_ms = lap(&mut start);
+    if options.output.set {
+        match panorama {
+            Some(panorama) => display_panorama_as_background(
+                panorama,
+                &background,
+                &output_path,
+                &options.output,
+            )?,
+            None => display_image_as_background(&output_path, &options.output)?,
+        };
+    }
+    timings.set_ms = lap(&mut start);
+    if let Some(palette_options) = &options.palette {
+        let palette = Palette::extract(&background);
+        if let Err(e) = palette.write(palette_options.directory.as_deref(), &output_path) {
+            eprintln!("{e}. The color palette was not written.");
+        }
+    }
+    if !options.output.to_stdout() {
+        let set = options.output.set;
+        match record(&output_path, options, &choices, set, layer.recorded) {
+            Ok(timestamp) => layer.recorded = Some(timestamp),
+            Err(e) => eprintln!("{e}. The wallpaper was not added to the history."),
+        }
     }
-    let mut output_path = application_data_path();
-    output_path.push("backgrounds");
-    std::fs::create_dir_all(&output_path).unwrap();
-    output_path.push(generate_file_name());
-    BackgroundImage::save(background, &output_path).expect("Failed to save background image.");
 
-    match display_image_as_background(&output_path) {
-        Ok(()) => println!("Updated wallpaper."),
-        Err(e) => println!("Failed to set wallpaper. {e}"),
+    let [red, green, blue, _] = text_config.color.0;
+    let mut report = RunReport {
+        path: output_path,
+        width: background.width(),
+        height: background.height(),
+        quote: text.first().filter(|_| is_quote).cloned(),
+        author: text.get(1).filter(|_| is_quote).cloned(),
+        text,
+        background_colors: choices.colors.iter().map(|color| hex(*color)).collect(),
+        text_color: hex([red, green, blue]),
+        source_url: choices.image_url,
+        photo_credit: choices.image_credit,
+        seed: layer.seed,
+        timings,
+    };
+    run_hooks(&options.post_hooks, &report);
+    report.timings.hooks_ms = lap(&mut start);
+
+    Ok(report)
+}
+
+/// Sets the wallpaper `steps` wallpapers back in the history again, as a panorama
+/// if it was generated as one.
+fn revert(steps: usize) -> Result<()> {
+    let entry = previous_entry(steps)?;
+    let panorama = if entry.options.display.panorama {
+        Panorama::new(&all_displays()?, entry.options.display.bezel_mm)
+    } else {
+        None
+    };
+    match &panorama {
+        Some(panorama) => {
+            let background = BackgroundImage::from(image::open(&entry.path)?.into_rgba8());
+            display_panorama_as_background(
+                panorama,
+                &background,
+                &entry.path,
+                &entry.options.output,
+            )?
+        }
+        None => display_image_as_background(&entry.path, &entry.options.output)?,
     };
+    record(&entry.path, &entry.options, &entry.choices, true, None)?;
+
+    println!(
+        "Reverted to the wallpaper of {}.",
+        entry.timestamp.format("%Y-%m-%d %H:%M:%S")
+    );
+    Ok(())
 }
 
-fn generate_file_name() -> String {
-    let mut rng = thread_rng();
+/// Writes the wallpaper to the output of the options, or to a new file in the
+/// application's data directory. Returns the path it was written to, "-" for stdout.
+fn save_wallpaper(
+    background: &BackgroundImage,
+    output: &OutputOptions,
+    rng: &mut StdRng,
+) -> Result<PathBuf> {
+    let bytes = background.encode(output)?;
+
+    let path = match &output.path {
+        Some(path) if output.to_stdout() => {
+            std::io::stdout().lock().write_all(&bytes)?;
+            return Ok(path.clone());
+        }
+        Some(path) => {
+            if let Some(parent) = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                std::fs::create_dir_all(parent)?;
+            }
+            path.clone()
+        }
+        None => {
+            let directory = backgrounds_path()?;
+            std::fs::create_dir_all(&directory)?;
+            loop {
+                let path = directory.join(generate_file_name(output.format.extension(), rng));
+                if !path.exists() {
+                    break path;
+                }
+            }
+        }
+    };
+    std::fs::write(&path, bytes)?;
+
+    // Wallpapers are set from absolute paths
+    Ok(std::fs::canonicalize(&path).unwrap_or(path))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_background(
+    background_options: &BackgroundOptions,
+    options: &Options,
+    width: u32,
+    height: u32,
+    scale_factor: f32,
+    preferences: &Preferences,
+    choices: &mut Choices,
+    rng: &mut StdRng,
+) -> Result<BackgroundImage> {
+    match background_options {
+        BackgroundOptions::Color(color) => {
+            let [red, green, blue, alpha] = if color == "random" {
+                let [red, green, blue] = preferences.random_color(rng);
+                [red, green, blue, 255]
+            } else if is_symbolic_color(color) {
+                return Err(Error::Config(format!(
+                    "The {color} color comes from an image, set one with --random-image, \
+                     --background-image, --sky or --svg"
+                )));
+            } else {
+                parse_color(color)?
+            };
+            choices.colors = vec![[red, green, blue]];
+            ColorSource::new(width, height, Rgba([red, green, blue, alpha])).get_background()
+        }
+        BackgroundOptions::RandomImage => {
+            require_network(options)?;
+            let image = get_random_image(preferences)?;
+            let image_source = ImageSource::new(width, height, image.url.clone());
+            let background = image_source.get_background()?;
+            choices.image_id = Some(image.id);
+            choices.image_url = Some(image.url);
+            choices.image_credit = Some(image.credit);
+            Ok(background)
+        }
+        BackgroundOptions::Url(url) => {
+            require_network(options)?;
+            let image_source = ImageSource::new(width, height, url.to_owned());
+            let background = image_source.get_background()?;
+            choices.image_url = Some(url.to_owned());
+            Ok(background)
+        }
+        BackgroundOptions::Sky => {
+            let location = options
+                .location
+                .or_else(Location::from_env)
+                .ok_or_else(missing_location)?;
+            let sky_source = SkySource::new(width, height, sun_position(&location, &Utc::now()));
+            sky_source.get_background()
+        }
+        BackgroundOptions::Gradient => {
+            let (top, bottom) = (preferences.random_color(rng), preferences.random_color(rng));
+            choices.colors = vec![top, bottom];
+            GradientSource::new(width, height, top, bottom).get_background()
+        }
+        BackgroundOptions::Svg { path, tile_width } => {
+            let tile_width =
+                tile_width.map(|tile_width| (tile_width * scale_factor).round() as u32);
+            let svg_source = SvgSource::new(width, height, path.clone(), tile_width);
+            svg_source.get_background()
+        }
+        BackgroundOptions::ImageColor { source, color } => {
+            let image = create_background(
+                source,
+                options,
+                width,
+                height,
+                scale_factor,
+                preferences,
+                choices,
+                rng,
+            )?;
+            let [red, green, blue, alpha] = ImageColors::extract(&image, None).resolve(color)?;
+            choices.colors = vec![[red, green, blue]];
+            ColorSource::new(width, height, Rgba([red, green, blue, alpha])).get_background()
+        }
+    }
+}
+
+fn create_text(
+    text_options: &TextOptions,
+    options: &Options,
+    preferences: &Preferences,
+    rng: &mut StdRng,
+) -> Result<Vec<String>> {
+    match text_options {
+        TextOptions::MessageFile(path) => {
+            let message_file_source = MessageFileSource::new(path.clone());
+            message_file_source.source_text()
+        }
+        TextOptions::MessageCommand { command, timeout } => {
+            let command_source = CommandSource::new(command.clone(), *timeout);
+            command_source.source_text()
+        }
+        TextOptions::Message(message) => {
+            if !message.is_empty() {
+                Ok(vec![message.to_owned()])
+            } else {
+                Ok(vec![])
+            }
+        }
+        TextOptions::RandomQuote => {
+            let offline_quote_source =
+                OfflineQuoteSource::new(options.quotes.clone(), preferences.clone(), rng);
+            offline_quote_source.source_text()
+        }
+        TextOptions::NetworkQuote => {
+            require_network(options)?;
+            let random_quote_source =
+                QuoteSource::new(options.quotes.clone(), preferences.clone(), rng);
+            random_quote_source.source_text()
+        }
+        TextOptions::RandomWord => {
+            let random_word_source = RandomWordSource::new(options.words.clone(), rng);
+            random_word_source.source_text()
+        }
+        TextOptions::Todo(todo_options) => {
+            let todo_source = TodoSource::new(todo_options.clone(), Local::now().date_naive());
+            todo_source.source_text()
+        }
+        TextOptions::Clock { formats, time_zone } => {
+            let time_zone = time_zone
+                .as_ref()
+                .map(|name| {
+                    name.parse::<Tz>()
+                        .map_err(|_| Error::Config(format!("Unknown time zone \"{name}\"")))
+                })
+                .transpose()?;
+            let clock_source = ClockSource::new(formats.clone(), time_zone, Utc::now());
+            clock_source.source_text()
+        }
+        TextOptions::WordOfTheDay(dictionary) => {
+            let word_of_the_day_source =
+                WordOfTheDaySource::new(dictionary.clone(), Local::now().date_naive());
+            word_of_the_day_source.source_text()
+        }
+        TextOptions::QuoteFile {
+            path,
+            format,
+            order,
+        } => {
+            let quote_file_source =
+                QuoteFileSource::new(path.clone(), *format, *order, options.quotes.clone(), rng);
+            quote_file_source.source_text()
+        }
+    }
+}
+
+/// Fails when the network must not be used.
+fn require_network(options: &Options) -> Result<()> {
+    if options.offline {
+        return Err(Error::Network("Offline mode is enabled".to_string()));
+    }
+    Ok(())
+}
+
+/// Name of a new wallpaper file, sorting by the time it was generated.
+fn generate_file_name(extension: &str, rng: &mut StdRng) -> String {
     let id = rng.sample(Uniform::new(1000, 9999));
+    let time = Local::now().format("%Y%m%d-%H%M%S");
 
-    format!("background_{id}.png")
+    format!("background_{time}_{id}.{extension}")
 }
 
-/// Sets the background image on all active desktops.
+/// Sets the background image on all active desktops, with the --set-command or
+/// the setter of the running desktop.
 ///
-/// Note: Setting the wallpaper of individual desktop is currently not supported by
-///       wallpaper. It can be done easily on MacOS to be consitent between platforms
-///       all desktops are set. This should eventually become a configuration options.
-fn display_image_as_background(image_path: &PathBuf) -> Result<(), Box<dyn Error>> {
-    wallpaper::set_from_path(image_path.to_str().unwrap())
+/// Note: Setting the wallpaper of individual desktop is currently not supported.
+///       It can be done easily on MacOS to be consitent between platforms all
+///       desktops are set. This should eventually become a configuration options.
+fn display_image_as_background(image_path: &Path, output: &OutputOptions) -> Result<()> {
+    wallpaper_setter(output.set_command.as_deref()).set(image_path, false)
 }
 
-/// Computes the dimensions of the primary display.
-fn get_display_resolution() -> Option<(u32, u32)> {
-    let displays = DisplayInfo::all().unwrap();
-    if let Some(primary) = displays.iter().filter(|display| display.is_primary).next() {
-        return Some((primary.width as u32, primary.height as u32));
+/// Sets a background spanning every display.
+///
+/// Windows, GNOME, XFCE and feh can span one image across all displays, while
+/// the swww, swaybg and Plasma setters fail. MacOS cannot either, so the panorama
+/// is cropped into one image per display and each desktop is set individually,
+/// unless a --set-command takes care of it.
+fn display_panorama_as_background(
+    panorama: &Panorama,
+    background: &BackgroundImage,
+    image_path: &Path,
+    output: &OutputOptions,
+) -> Result<()> {
+    if !cfg!(target_os = "macos") || output.set_command.is_some() {
+        return wallpaper_setter(output.set_command.as_deref()).set(image_path, true);
+    }
+
+    for (index, region) in panorama.regions.iter().enumerate() {
+        let crop_path = image_path.with_extension(format!("{index}.png"));
+        background.crop(region).save(&crop_path)?;
+
+        // AppleScript desktops are 1-indexed
+        let script = format!(
+            r#"tell application "System Events" to set picture of desktop {} to "{}""#,
+            index + 1,
+            crop_path.display()
+        );
+        let status = process::Command::new("osascript")
+            .args(["-e", &script])
+            .status()?;
+        if !status.success() {
+            return Err(Error::Wallpaper(format!(
+                "osascript failed for display {}",
+                index + 1
+            )));
+        }
     }
 
-    None
+    Ok(())
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BackgroundImage {
     width: u32,
     height: u32,
@@ -176,43 +779,211 @@ impl BackgroundImage {
         self.height
     }
 
+    pub fn get_pixel(&self, x: u32, y: u32) -> &Rgba<u8> {
+        self.buffer.get_pixel(x, y)
+    }
+
     /// Sets the color of a given pixel
     pub fn set_pixel(&mut self, x: u32, y: u32, color: &Rgba<u8>) {
         if x >= self.width() || y >= self.height() {
             return;
         }
-        self.buffer.get_pixel_mut(x, y).blend(&color);
+        self.buffer.get_pixel_mut(x, y).blend(color);
+    }
+
+    /// Blends a color over a rectangle of the image
+    pub fn fill_rect(&mut self, region: &Region, color: &Rgba<u8>) {
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                self.set_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Draws antialiased shapes, positioned relative to the top left corner of
+    /// a region and scaled by the scale factor of its display
+    pub fn draw_shapes(
+        &mut self,
+        shapes: &[Shape],
+        region: &Region,
+        scale_factor: f32,
+        colors: &SymbolicColors,
+    ) -> Result<()> {
+        let pixmap = render_shapes(shapes, region, scale_factor, colors)?;
+        self.draw_pixmap(&pixmap, region);
+
+        Ok(())
+    }
+
+    /// Blends an antialiased rectangle with rounded corners over a region of
+    /// the image
+    pub fn fill_rounded_rect(&mut self, region: &Region, radius: f32, color: &Rgba<u8>) {
+        match render_rounded_rect(region, radius, color.0) {
+            Ok(pixmap) => self.draw_pixmap(&pixmap, region),
+            Err(e) => eprintln!("{e}. Skipping the rectangle."),
+        }
+    }
+
+    /// Blends a rendered pixmap over the image with its top left corner at the
+    /// top left corner of a region
+    fn draw_pixmap(&mut self, pixmap: &Pixmap, region: &Region) {
+        for (index, pixel) in pixmap.pixels().iter().enumerate() {
+            if pixel.alpha() == 0 {
+                continue;
+            }
+            let x = region.x + index as u32 % pixmap.width();
+            let y = region.y + index as u32 / pixmap.width();
+            self.set_pixel(x, y, &Rgba(unpremultiply(*pixel)));
+        }
+    }
+
+    /// Blends an image over this one with its top left corner at (x, y), its
+    /// alpha multiplied by `opacity`
+    pub fn draw_image(&mut self, image: &RgbaImage, x: u32, y: u32, opacity: f32) {
+        for (image_x, image_y, pixel) in image.enumerate_pixels() {
+            let alpha = (pixel.0[3] as f32 * opacity.clamp(0.0, 1.0)).round() as u8;
+            if alpha == 0 {
+                continue;
+            }
+            let color = Rgba([pixel.0[0], pixel.0[1], pixel.0[2], alpha]);
+            self.set_pixel(x + image_x, y + image_y, &color);
+        }
+    }
+
+    /// Copies a region of the image into a new image
+    pub fn crop(&self, region: &Region) -> BackgroundImage {
+        let buffer = image::imageops::crop_imm(
+            &self.buffer,
+            region.x,
+            region.y,
+            region.width,
+            region.height,
+        )
+        .to_image();
+
+        BackgroundImage::from(buffer)
+    }
+
+    pub fn save(&self, path: &Path) -> ImageResult<()> {
+        self.buffer.save(path)
     }
 
-    pub fn save(image: BackgroundImage, path: &PathBuf) -> ImageResult<()> {
-        image.buffer.save(path)
+    /// Encodes the image in the format of the output options
+    pub fn encode(&self, output: &OutputOptions) -> ImageResult<Vec<u8>> {
+        let mut bytes = Cursor::new(vec![]);
+        let (width, height) = (self.width, self.height);
+        match output.format {
+            OutputFormat::Png => {
+                let compression = match output.compression {
+                    PngCompression::Fast => CompressionType::Fast,
+                    PngCompression::Default => CompressionType::Default,
+                    PngCompression::Best => CompressionType::Best,
+                };
+                PngEncoder::new_with_quality(&mut bytes, compression, PngFilter::Adaptive)
+                    .write_image(&self.buffer, width, height, ColorType::Rgba8)?;
+            }
+            OutputFormat::Jpeg => {
+                // JPEG has no alpha channel
+                let rgb = DynamicImage::ImageRgba8(self.buffer.clone()).into_rgb8();
+                JpegEncoder::new_with_quality(&mut bytes, output.quality).write_image(
+                    &rgb,
+                    width,
+                    height,
+                    ColorType::Rgb8,
+                )?;
+            }
+            OutputFormat::Webp => {
+                let encoder = webp::Encoder::from_rgba(&self.buffer, width, height);
+                let encoded = match output.quality {
+                    100 => encoder.encode_lossless(),
+                    quality => encoder.encode(quality as f32),
+                };
+                bytes.write_all(&encoded)?;
+            }
+            OutputFormat::Bmp => {
+                BmpEncoder::new(&mut bytes).write_image(
+                    &self.buffer,
+                    width,
+                    height,
+                    ColorType::Rgba8,
+                )?;
+            }
+            OutputFormat::Tiff => {
+                TiffEncoder::new(&mut bytes).write_image(
+                    &self.buffer,
+                    width,
+                    height,
+                    ColorType::Rgba8,
+                )?;
+            }
+        }
+
+        Ok(bytes.into_inner())
     }
 }
 
+/// Number of random Unsplash photos fetched before giving up on finding one
+/// that was not disliked.
+const IMAGE_ATTEMPTS: usize = 5;
+
 #[derive(Deserialize)]
 struct UnsplashResponse {
+    id: String,
     urls: HashMap<String, String>,
+    user: UnsplashUser,
+}
+
+#[derive(Deserialize)]
+struct UnsplashUser {
+    name: String,
+    links: HashMap<String, String>,
 }
 
-fn get_random_image() -> String {
+struct UnsplashImage {
+    id: String,
+    url: String,
+    credit: PhotoCredit,
+}
+
+/// Random Unsplash photo, other than the disliked ones.
+fn get_random_image(preferences: &Preferences) -> Result<UnsplashImage> {
     // Loads the environment variables from .env
     // .env will contents will override any existing UNSPLASH_API_KEY environment variables.
     dotenv().ok();
 
-    let api_key = std::env::var("UNSPLASH_API_KEY").unwrap();
+    let api_key =
+        std::env::var("UNSPLASH_API_KEY").map_err(|_| Error::MissingApiKey("UNSPLASH_API_KEY"))?;
     let endpoint = format!("https://api.unsplash.com/photos/random?client_id={api_key}");
-    let response = reqwest::blocking::get(&endpoint)
-        .unwrap()
-        .json::<UnsplashResponse>()
-        .unwrap();
+    for _ in 0..IMAGE_ATTEMPTS {
+        let response = reqwest::blocking::get(&endpoint)?
+            .error_for_status()?
+            .json::<UnsplashResponse>()?;
+        if preferences.is_disliked_image(&response.id) {
+            continue;
+        }
+
+        let url = response.urls.get("full").ok_or_else(|| {
+            Error::Network("Unsplash response is missing the image url".to_string())
+        })?;
+        return Ok(UnsplashImage {
+            id: response.id,
+            url: url.to_owned(),
+            credit: PhotoCredit {
+                name: response.user.name,
+                profile_url: response.user.links.get("html").cloned().unwrap_or_default(),
+            },
+        });
+    }
 
-    response.urls.get(&"full".to_string()).unwrap().to_owned()
+    Err(Error::Network(format!(
+        "The last {IMAGE_ATTEMPTS} Unsplash photos were all disliked"
+    )))
 }
 
-fn parse_color(raw_color: &str) -> Option<[u8; 4]> {
+fn parse_color(raw_color: &str) -> Result<[u8; 4]> {
     let parsed_color = csscolorparser::parse(raw_color);
     match parsed_color {
-        Ok(color) => Some(color.to_rgba8()),
-        _ => None,
+        Ok(color) => Ok(color.to_rgba8()),
+        _ => Err(Error::InvalidColor(raw_color.to_string())),
     }
 }
@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use image::imageops::FilterType;
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::args::Anchor;
+use crate::calendar::anchor_position;
+use crate::display::Region;
+use crate::error::Result;
+use crate::svg::Svg;
+use crate::BackgroundImage;
+
+/// Image (PNG, WebP, JPEG, ...) or SVG file drawn over the background.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Overlay {
+    pub path: PathBuf,
+    /// Position on the primary display
+    #[serde(default = "default_position")]
+    pub position: Anchor,
+    /// Width in points. The aspect ratio is kept when only one of the width and
+    /// height is set
+    #[serde(default)]
+    pub width: Option<f32>,
+    /// Height in points
+    #[serde(default)]
+    pub height: Option<f32>,
+    /// Width relative to the width of the primary display, used when neither the
+    /// width nor the height is set. Images keep their own size if [None]
+    #[serde(default)]
+    pub scale: Option<f32>,
+    /// Clockwise rotation in degrees
+    #[serde(default)]
+    pub rotation: f32,
+    /// From 0 (invisible) to 1 (opaque)
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+}
+
+fn default_position() -> Anchor {
+    Anchor::Center
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+impl Overlay {
+    /// Parses an overlay from its JSON description.
+    pub fn parse(raw_overlay: &str) -> std::result::Result<Self, String> {
+        serde_json::from_str(raw_overlay).map_err(|e| format!("Invalid overlay {raw_overlay}. {e}"))
+    }
+
+    /// Size of the overlay in pixels, given the intrinsic size of its file in points.
+    fn pixel_size(&self, intrinsic: (f32, f32), region: &Region, scale_factor: f32) -> (u32, u32) {
+        let (intrinsic_width, intrinsic_height) = intrinsic;
+        let aspect_ratio = intrinsic_width / intrinsic_height;
+        let (width, height) = match (self.width, self.height, self.scale) {
+            (Some(width), Some(height), _) => (width, height),
+            (Some(width), None, _) => (width, width / aspect_ratio),
+            (None, Some(height), _) => (height * aspect_ratio, height),
+            (None, None, Some(scale)) => {
+                let width = region.width as f32 * scale / scale_factor;
+                (width, width / aspect_ratio)
+            }
+            (None, None, None) => (intrinsic_width, intrinsic_height),
+        };
+
+        (
+            ((width * scale_factor).round() as u32).max(1),
+            ((height * scale_factor).round() as u32).max(1),
+        )
+    }
+}
+
+fn is_svg(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"))
+}
+
+/// Rasterizes or resizes an overlay for the display of `region` and draws it at
+/// its position in the region.
+pub fn draw_overlay(
+    image: &mut BackgroundImage,
+    overlay: &Overlay,
+    region: &Region,
+    scale_factor: f32,
+) -> Result<()> {
+    let resized = if is_svg(&overlay.path) {
+        let svg = Svg::open(&overlay.path)?;
+        let (width, height) = overlay.pixel_size(svg.size(), region, scale_factor);
+        svg.render(width, height)?
+    } else {
+        let decoded = image::open(&overlay.path)?.into_rgba8();
+        // Images are drawn pixel for pixel by default
+        let intrinsic = (
+            decoded.width() as f32 / scale_factor,
+            decoded.height() as f32 / scale_factor,
+        );
+        let (width, height) = overlay.pixel_size(intrinsic, region, scale_factor);
+        if (width, height) == decoded.dimensions() {
+            decoded
+        } else {
+            image::imageops::resize(&decoded, width, height, FilterType::CatmullRom)
+        }
+    };
+    let rotated = rotate(&resized, overlay.rotation);
+
+    let (x, y) = anchor_position(region, overlay.position, rotated.width(), rotated.height());
+    image.draw_image(&rotated, x, y, overlay.opacity);
+
+    Ok(())
+}
+
+/// Rotates an image clockwise around its center, enlarging it to fit the corners.
+fn rotate(image: &RgbaImage, degrees: f32) -> RgbaImage {
+    if degrees % 360.0 == 0.0 {
+        return image.clone();
+    }
+
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let rotated_width = (width * cos.abs() + height * sin.abs()).ceil() as u32;
+    let rotated_height = (width * sin.abs() + height * cos.abs()).ceil() as u32;
+
+    let mut rotated = RgbaImage::new(rotated_width, rotated_height);
+    let (center_x, center_y) = (rotated_width as f32 / 2.0, rotated_height as f32 / 2.0);
+    for (x, y, pixel) in rotated.enumerate_pixels_mut() {
+        // Point of the original image that ends up at the center of this pixel
+        let dx = x as f32 + 0.5 - center_x;
+        let dy = y as f32 + 0.5 - center_y;
+        let source_x = dx * cos + dy * sin + width / 2.0 - 0.5;
+        let source_y = -dx * sin + dy * cos + height / 2.0 - 0.5;
+        *pixel = sample(image, source_x, source_y);
+    }
+
+    rotated
+}
+
+/// Bilinear sample of an image, transparent outside of it. Colors are weighted
+/// by their alpha so transparent pixels don't darken the edges.
+fn sample(image: &RgbaImage, x: f32, y: f32) -> Rgba<u8> {
+    let (left, top) = (x.floor(), y.floor());
+    let (fraction_x, fraction_y) = (x - left, y - top);
+    let neighbours = [
+        (0, 0, (1.0 - fraction_x) * (1.0 - fraction_y)),
+        (1, 0, fraction_x * (1.0 - fraction_y)),
+        (0, 1, (1.0 - fraction_x) * fraction_y),
+        (1, 1, fraction_x * fraction_y),
+    ];
+
+    let mut sum = [0.0; 4];
+    for (offset_x, offset_y, weight) in neighbours {
+        let neighbour_x = left as i64 + offset_x;
+        let neighbour_y = top as i64 + offset_y;
+        if neighbour_x < 0
+            || neighbour_y < 0
+            || neighbour_x >= image.width() as i64
+            || neighbour_y >= image.height() as i64
+        {
+            continue;
+        }
+
+        let [red, green, blue, alpha] = image.get_pixel(neighbour_x as u32, neighbour_y as u32).0;
+        let alpha = alpha as f32 * weight;
+        sum[0] += red as f32 * alpha;
+        sum[1] += green as f32 * alpha;
+        sum[2] += blue as f32 * alpha;
+        sum[3] += alpha;
+    }
+
+    if sum[3] <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    Rgba([
+        (sum[0] / sum[3]).round() as u8,
+        (sum[1] / sum[3]).round() as u8,
+        (sum[2] / sum[3]).round() as u8,
+        sum[3].round() as u8,
+    ])
+}
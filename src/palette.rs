@@ -0,0 +1,666 @@
+use std::cell::OnceCell;
+use std::path::{Path, PathBuf};
+
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use crate::display::Region;
+use crate::error::{Error, Result};
+use crate::report::hex;
+use crate::utils::application_data_path;
+use crate::{parse_color, BackgroundImage};
+
+/// Number of pixels sampled from the wallpaper, spread over a grid.
+const SAMPLES: u32 = 16_384;
+/// Number of color clusters: the background, the foreground and six accents.
+const CLUSTERS: usize = 8;
+const KMEANS_ITERATIONS: usize = 10;
+/// Minimum WCAG contrast ratio of the text colors against the background.
+const TEXT_CONTRAST: f32 = 4.5;
+/// Minimum contrast of the bright black used for comments and other dim text.
+const DIM_CONTRAST: f32 = 3.0;
+/// Colors with a chroma below this have no noticeable hue.
+const GRAY_CHROMA: f32 = 0.03;
+/// Chroma of the accents made up for gray wallpapers.
+const MUTED_CHROMA: f32 = 0.08;
+/// Minimum Oklab distance between the accent and the dominant color of an image.
+const MIN_ACCENT_DISTANCE: f32 = 0.1;
+/// Hues in Oklab of the red, green, yellow, blue, magenta and cyan terminal colors.
+const ACCENT_HUES: [f32; 6] = [29.0, 142.0, 110.0, 264.0, 328.0, 195.0];
+const NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Color in the Oklab perceptual color space, where distances match how
+/// different colors look.
+#[derive(Debug, Clone, Copy, Default)]
+struct Lab {
+    l: f32,
+    a: f32,
+    b: f32,
+}
+
+fn to_linear(channel: u8) -> f32 {
+    let channel = channel as f32 / 255.0;
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(channel: f32) -> u8 {
+    let channel = channel.clamp(0.0, 1.0);
+    let channel = if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    };
+    (channel * 255.0).round() as u8
+}
+
+impl Lab {
+    fn from_rgb([red, green, blue]: [u8; 3]) -> Self {
+        let (red, green, blue) = (to_linear(red), to_linear(green), to_linear(blue));
+        let l = (0.412_221_46 * red + 0.536_332_55 * green + 0.051_445_995 * blue).cbrt();
+        let m = (0.211_903_5 * red + 0.680_699_5 * green + 0.107_396_96 * blue).cbrt();
+        let s = (0.088_302_46 * red + 0.281_718_85 * green + 0.629_978_7 * blue).cbrt();
+
+        Self {
+            l: 0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+            a: 1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+            b: 0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+        }
+    }
+
+    /// Nearest sRGB color, clipping colors outside of the sRGB gamut.
+    fn to_rgb(self) -> [u8; 3] {
+        let l = (self.l + 0.396_337_78 * self.a + 0.215_803_76 * self.b).powi(3);
+        let m = (self.l - 0.105_561_346 * self.a - 0.063_854_17 * self.b).powi(3);
+        let s = (self.l - 0.089_484_18 * self.a - 1.291_485_5 * self.b).powi(3);
+
+        [
+            from_linear(4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s),
+            from_linear(-1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s),
+            from_linear(-0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s),
+        ]
+    }
+
+    fn chroma(self) -> f32 {
+        self.a.hypot(self.b)
+    }
+
+    /// Hue in degrees.
+    fn hue(self) -> f32 {
+        self.b.atan2(self.a).to_degrees().rem_euclid(360.0)
+    }
+
+    fn with_lightness(self, l: f32) -> Self {
+        Self {
+            l: l.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    fn with_chroma_scale(self, scale: f32) -> Self {
+        Self {
+            a: self.a * scale,
+            b: self.b * scale,
+            ..self
+        }
+    }
+
+    fn distance(self, other: Self) -> f32 {
+        (self.l - other.l).powi(2) + (self.a - other.a).powi(2) + (self.b - other.b).powi(2)
+    }
+}
+
+/// WCAG relative luminance of an sRGB color.
+fn luminance([red, green, blue]: [u8; 3]) -> f32 {
+    0.2126 * to_linear(red) + 0.7152 * to_linear(green) + 0.0722 * to_linear(blue)
+}
+
+/// WCAG contrast ratio, from 1 (none) to 21 (black on white).
+fn contrast(a: [u8; 3], b: [u8; 3]) -> f32 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Lightens a color until it reaches `ratio` of contrast against the background,
+/// or darkens it on backgrounds light enough that white contrasts less than black.
+fn ensure_contrast(color: Lab, background: Lab, ratio: f32) -> Lab {
+    let background = background.to_rgb();
+    let lighten = contrast([255; 3], background) >= contrast([0; 3], background);
+    let (step, limit) = if lighten { (0.02, 1.0) } else { (-0.02, 0.0) };
+    let mut color = color;
+    while color.l != limit && contrast(color.to_rgb(), background) < ratio {
+        color = color.with_lightness(color.l + step);
+    }
+    color
+}
+
+/// Terminal color scheme: the 16 ANSI colors and the special colors.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub background: [u8; 3],
+    pub foreground: [u8; 3],
+    pub cursor: [u8; 3],
+    /// Black, red, green, yellow, blue, magenta, cyan and white, then their bright variants
+    pub colors: [[u8; 3]; 16],
+}
+
+/// Colors sampled from a grid over the image, or over a region of it.
+fn sample(image: &BackgroundImage, region: Option<&Region>) -> Vec<Lab> {
+    let (left, top) = region.map_or((0, 0), |region| (region.x, region.y));
+    let right = region.map_or(image.width(), |region| region.x + region.width);
+    let bottom = region.map_or(image.height(), |region| region.y + region.height);
+    let (right, bottom) = (right.min(image.width()), bottom.min(image.height()));
+    let area = right.saturating_sub(left) as u64 * bottom.saturating_sub(top) as u64;
+    let step = ((area as f64 / SAMPLES as f64).sqrt() as u32).max(1);
+
+    let mut samples = vec![];
+    for y in (top..bottom).step_by(step as usize) {
+        for x in (left..right).step_by(step as usize) {
+            let [red, green, blue, _] = image.get_pixel(x, y).0;
+            samples.push(Lab::from_rgb([red, green, blue]));
+        }
+    }
+    samples
+}
+
+fn mean(colors: &[Lab]) -> Lab {
+    let count = colors.len().max(1) as f32;
+    let sum = colors.iter().fold(Lab::default(), |sum, color| Lab {
+        l: sum.l + color.l,
+        a: sum.a + color.a,
+        b: sum.b + color.b,
+    });
+    Lab {
+        l: sum.l / count,
+        a: sum.a / count,
+        b: sum.b / count,
+    }
+}
+
+/// Initial clusters from a median cut: the box of colors with the widest
+/// spread is split in half along that axis until there are `count` boxes.
+fn median_cut(samples: &[Lab], count: usize) -> Vec<Lab> {
+    let channel = |color: &Lab, axis: usize| match axis {
+        0 => color.l,
+        1 => color.a,
+        _ => color.b,
+    };
+    let spread = |colors: &[Lab], axis: usize| {
+        let values = colors.iter().map(|color| channel(color, axis));
+        let (min, max) = values.fold((f32::MAX, f32::MIN), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+        max - min
+    };
+
+    let mut boxes = vec![samples.to_vec()];
+    while boxes.len() < count {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(index, colors)| {
+                let axis = (0..3)
+                    .max_by(|a, b| spread(colors, *a).total_cmp(&spread(colors, *b)))
+                    .unwrap_or(0);
+                (index, axis, spread(colors, axis) * colors.len() as f32)
+            })
+            .max_by(|a, b| a.2.total_cmp(&b.2));
+        let Some((index, axis, _)) = widest else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by(|a, b| channel(a, axis).total_cmp(&channel(b, axis)));
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|colors| mean(colors)).collect()
+}
+
+/// Groups the samples into clusters of similar colors with k-means, starting
+/// from a median cut so the result doesn't change between runs. Returns the
+/// colors of the clusters with their number of samples.
+fn cluster(samples: &[Lab]) -> Vec<(Lab, usize)> {
+    let mut centroids = median_cut(samples, CLUSTERS);
+    let mut sizes = vec![0; centroids.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut members: Vec<Vec<Lab>> = vec![vec![]; centroids.len()];
+        for sample in samples {
+            let nearest = (0..centroids.len())
+                .min_by(|a, b| {
+                    sample
+                        .distance(centroids[*a])
+                        .total_cmp(&sample.distance(centroids[*b]))
+                })
+                .unwrap_or(0);
+            members[nearest].push(*sample);
+        }
+        for (centroid, members) in centroids.iter_mut().zip(&members) {
+            if !members.is_empty() {
+                *centroid = mean(members);
+            }
+        }
+        sizes = members.iter().map(Vec::len).collect();
+    }
+
+    let mut clusters: Vec<(Lab, usize)> = centroids.into_iter().zip(sizes).collect();
+    // A uniform image has fewer distinct colors than clusters
+    while clusters.len() < CLUSTERS {
+        let last = clusters.last().map_or(Lab::default(), |(color, _)| *color);
+        clusters.push((last, 0));
+    }
+    clusters
+}
+
+/// Distance between two hues in degrees.
+fn hue_distance(a: f32, b: f32) -> f32 {
+    let distance = (a - b).abs();
+    distance.min(360.0 - distance)
+}
+
+/// Assigns the accent colors to the red, green, yellow, blue, magenta and cyan
+/// slots, closest hues first. Slots left with a gray get a muted color of their
+/// own hue, so the terminal colors stay distinguishable on gray wallpapers.
+fn assign_accents(accents: &[Lab]) -> [Lab; 6] {
+    let mut pairs: Vec<(usize, usize, f32)> = vec![];
+    for (slot, target) in ACCENT_HUES.iter().enumerate() {
+        for (index, accent) in accents.iter().enumerate() {
+            let distance = if accent.chroma() < GRAY_CHROMA {
+                f32::MAX
+            } else {
+                hue_distance(accent.hue(), *target)
+            };
+            pairs.push((slot, index, distance));
+        }
+    }
+    pairs.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+    let mut slots: [Option<Lab>; 6] = [None; 6];
+    let mut used = vec![false; accents.len()];
+    for (slot, index, _) in pairs {
+        if slots[slot].is_none() && !used[index] {
+            slots[slot] = Some(accents[index]);
+            used[index] = true;
+        }
+    }
+
+    let mut accents = [Lab::default(); 6];
+    for (slot, assigned) in slots.iter().enumerate() {
+        let accent = assigned.unwrap_or_default();
+        accents[slot] = if accent.chroma() < GRAY_CHROMA {
+            let (sin, cos) = ACCENT_HUES[slot].to_radians().sin_cos();
+            Lab {
+                l: accent.l,
+                a: MUTED_CHROMA * cos,
+                b: MUTED_CHROMA * sin,
+            }
+        } else {
+            accent
+        };
+    }
+    accents
+}
+
+#[derive(Serialize)]
+struct PywalColors<'a> {
+    wallpaper: &'a Path,
+    special: SpecialColors,
+    colors: NumberedColors<'a>,
+}
+
+#[derive(Serialize)]
+struct SpecialColors {
+    background: String,
+    foreground: String,
+    cursor: String,
+}
+
+/// Colors as "color0" to "color15", in order.
+struct NumberedColors<'a>(&'a [[u8; 3]; 16]);
+
+impl Serialize for NumberedColors<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (index, color) in self.0.iter().enumerate() {
+            map.serialize_entry(&format!("color{index}"), &hex(*color))?;
+        }
+        map.end()
+    }
+}
+
+impl Palette {
+    /// Extracts a dark terminal color scheme from the colors of an image. The
+    /// text colors are lightened until they are readable on the background.
+    pub fn extract(image: &BackgroundImage) -> Self {
+        let mut clusters: Vec<Lab> = cluster(&sample(image, None))
+            .into_iter()
+            .map(|(color, _)| color)
+            .collect();
+        clusters.sort_by(|a, b| a.l.total_cmp(&b.l));
+        let darkest = clusters[0];
+        let lightest = clusters[clusters.len() - 1];
+
+        let background = darkest
+            .with_lightness(darkest.l.min(0.22))
+            .with_chroma_scale(0.6);
+        let foreground = ensure_contrast(
+            lightest
+                .with_lightness(lightest.l.max(0.92))
+                .with_chroma_scale(0.3),
+            background,
+            TEXT_CONTRAST,
+        );
+
+        let accents = assign_accents(&clusters[1..clusters.len() - 1]);
+        let normal = accents.map(|accent| {
+            // Leave room for the bright variants
+            let accent = accent.with_lightness(accent.l.clamp(0.55, 0.8));
+            ensure_contrast(accent, background, TEXT_CONTRAST)
+        });
+        let bright = normal.map(|accent| accent.with_lightness(accent.l + 0.1));
+        let dim = ensure_contrast(
+            background.with_lightness(background.l + 0.25),
+            background,
+            DIM_CONTRAST,
+        );
+        let white = ensure_contrast(
+            foreground.with_lightness(foreground.l - 0.12),
+            background,
+            TEXT_CONTRAST,
+        );
+
+        let mut colors = [[0; 3]; 16];
+        colors[0] = background.to_rgb();
+        colors[7] = white.to_rgb();
+        colors[8] = dim.to_rgb();
+        colors[15] = foreground.to_rgb();
+        for (index, (normal, bright)) in normal.iter().zip(&bright).enumerate() {
+            colors[index + 1] = normal.to_rgb();
+            colors[index + 9] = bright.to_rgb();
+        }
+
+        Self {
+            background: colors[0],
+            foreground: colors[15],
+            cursor: colors[15],
+            colors,
+        }
+    }
+
+    fn xresources(&self) -> String {
+        let mut lines = vec![
+            format!("*.background: {}", hex(self.background)),
+            format!("*.foreground: {}", hex(self.foreground)),
+            format!("*.cursorColor: {}", hex(self.cursor)),
+        ];
+        for (index, color) in self.colors.iter().enumerate() {
+            lines.push(format!("*.color{index}: {}", hex(*color)));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    fn kitty(&self) -> String {
+        let mut lines = vec![
+            format!("background {}", hex(self.background)),
+            format!("foreground {}", hex(self.foreground)),
+            format!("cursor {}", hex(self.cursor)),
+            format!("selection_background {}", hex(self.foreground)),
+            format!("selection_foreground {}", hex(self.background)),
+        ];
+        for (index, color) in self.colors.iter().enumerate() {
+            lines.push(format!("color{index} {}", hex(*color)));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    fn alacritty(&self) -> String {
+        let table = |name: &str, colors: &[[u8; 3]]| {
+            let mut lines = vec![format!("[colors.{name}]")];
+            for (name, color) in NAMES.iter().zip(colors) {
+                lines.push(format!("{name} = \"{}\"", hex(*color)));
+            }
+            lines.join("\n")
+        };
+
+        [
+            format!(
+                "[colors.primary]\nbackground = \"{}\"\nforeground = \"{}\"",
+                hex(self.background),
+                hex(self.foreground)
+            ),
+            format!(
+                "[colors.cursor]\ntext = \"{}\"\ncursor = \"{}\"",
+                hex(self.background),
+                hex(self.cursor)
+            ),
+            table("normal", &self.colors[..8]),
+            table("bright", &self.colors[8..]),
+        ]
+        .join("\n\n")
+            + "\n"
+    }
+
+    fn wezterm(&self) -> String {
+        let list = |colors: &[[u8; 3]]| {
+            let colors: Vec<String> = colors
+                .iter()
+                .map(|color| format!("\"{}\"", hex(*color)))
+                .collect();
+            format!("[{}]", colors.join(", "))
+        };
+
+        format!(
+            "[colors]\nbackground = \"{background}\"\nforeground = \"{foreground}\"\n\
+             cursor_bg = \"{cursor}\"\ncursor_fg = \"{background}\"\ncursor_border = \"{cursor}\"\n\
+             selection_bg = \"{foreground}\"\nselection_fg = \"{background}\"\n\
+             ansi = {ansi}\nbrights = {brights}\n\n[metadata]\nname = \"macground\"\n",
+            background = hex(self.background),
+            foreground = hex(self.foreground),
+            cursor = hex(self.cursor),
+            ansi = list(&self.colors[..8]),
+            brights = list(&self.colors[8..]),
+        )
+    }
+
+    /// Same layout as pywal's colors.json, which many tools already read.
+    fn json(&self, wallpaper: &Path) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&PywalColors {
+            wallpaper,
+            special: SpecialColors {
+                background: hex(self.background),
+                foreground: hex(self.foreground),
+                cursor: hex(self.cursor),
+            },
+            colors: NumberedColors(&self.colors),
+        })?)
+    }
+
+    fn css(&self, wallpaper: &Path) -> String {
+        let mut lines = vec![
+            ":root {".to_string(),
+            format!("  --wallpaper: url(\"{}\");", wallpaper.display()),
+            format!("  --background: {};", hex(self.background)),
+            format!("  --foreground: {};", hex(self.foreground)),
+            format!("  --cursor: {};", hex(self.cursor)),
+        ];
+        for (index, color) in self.colors.iter().enumerate() {
+            lines.push(format!("  --color{index}: {};", hex(*color)));
+        }
+        lines.push("}".to_string());
+        lines.join("\n") + "\n"
+    }
+
+    /// Writes the palette for Xresources, kitty, Alacritty, WezTerm and as JSON
+    /// and CSS variables to `directory`, "palette" in the data directory if [None].
+    /// Returns the directory.
+    pub fn write(&self, directory: Option<&Path>, wallpaper: &Path) -> Result<PathBuf> {
+        let directory = match directory {
+            Some(directory) => directory.to_path_buf(),
+            None => application_data_path()?.join("palette"),
+        };
+        std::fs::create_dir_all(&directory)?;
+
+        let files = [
+            ("colors.Xresources", self.xresources()),
+            ("colors-kitty.conf", self.kitty()),
+            ("colors-alacritty.toml", self.alacritty()),
+            ("colors-wezterm.toml", self.wezterm()),
+            ("colors.json", self.json(wallpaper)?),
+            ("colors.css", self.css(wallpaper)),
+        ];
+        for (name, contents) in files {
+            std::fs::write(directory.join(name), contents)?;
+        }
+
+        Ok(directory)
+    }
+}
+
+/// Most common and most vivid colors of a background, which the "dominant" and
+/// "accent" symbolic colors stand for.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageColors {
+    pub dominant: [u8; 3],
+    pub accent: [u8; 3],
+}
+
+impl ImageColors {
+    /// Colors of the image, or of a region of it. The accent is the most vivid
+    /// color covering enough of the image to belong to it, or on gray images
+    /// the one standing out most from the dominant color.
+    pub fn extract(image: &BackgroundImage, region: Option<&Region>) -> Self {
+        let clusters = cluster(&sample(image, region));
+        let total = clusters.iter().map(|(_, size)| size).sum::<usize>().max(1) as f32;
+        let (dominant, _) = clusters
+            .iter()
+            .copied()
+            .max_by_key(|(_, size)| *size)
+            .unwrap_or_default();
+
+        let candidates: Vec<(Lab, f32)> = clusters
+            .iter()
+            .filter(|(color, size)| {
+                *size > 0 && color.distance(dominant) > MIN_ACCENT_DISTANCE.powi(2)
+            })
+            .map(|(color, size)| (*color, (*size as f32 / total).sqrt()))
+            .collect();
+        let colorful = candidates
+            .iter()
+            .any(|(color, _)| color.chroma() >= GRAY_CHROMA);
+        let score = |(color, weight): &(Lab, f32)| {
+            let standout = if colorful {
+                color.chroma()
+            } else {
+                (color.l - dominant.l).abs()
+            };
+            standout * weight
+        };
+        let accent = match candidates
+            .iter()
+            .max_by(|a, b| score(a).total_cmp(&score(b)))
+        {
+            Some((accent, _)) => *accent,
+            // Uniform images only have shades of their color
+            None if dominant.l < 0.5 => dominant.with_lightness(dominant.l + 0.3),
+            None => dominant.with_lightness(dominant.l - 0.3),
+        };
+
+        Self {
+            dominant: dominant.to_rgb(),
+            accent: accent.to_rgb(),
+        }
+    }
+
+    /// Color named by a symbolic color, with its alpha.
+    pub fn resolve(&self, raw_color: &str) -> Result<[u8; 4]> {
+        let [red, green, blue] = match symbolic_name(raw_color) {
+            "accent" => self.accent,
+            _ => self.dominant,
+        };
+        Ok([red, green, blue, symbolic_alpha(raw_color)?])
+    }
+}
+
+fn symbolic_name(raw_color: &str) -> &str {
+    raw_color.split('/').next().unwrap_or_default().trim()
+}
+
+/// Whether the color is a symbolic color, "dominant" or "accent", optionally
+/// followed by an alpha as in "dominant / 0.6" or "accent / 60%".
+pub fn is_symbolic_color(raw_color: &str) -> bool {
+    matches!(symbolic_name(raw_color), "dominant" | "accent")
+}
+
+/// Alpha of a symbolic color, opaque if it has none.
+fn symbolic_alpha(raw_color: &str) -> Result<u8> {
+    let Some((_, alpha)) = raw_color.split_once('/') else {
+        return Ok(255);
+    };
+    let alpha = alpha.trim();
+    let alpha = match alpha.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f32>().map(|percent| percent / 100.0),
+        None => alpha.parse::<f32>(),
+    };
+    match alpha {
+        Ok(alpha) if (0.0..=1.0).contains(&alpha) => Ok((alpha * 255.0).round() as u8),
+        _ => Err(Error::InvalidColor(raw_color.to_string())),
+    }
+}
+
+/// Parses colors drawn over a background, where the symbolic colors are colors
+/// of the background. They are only extracted once a symbolic color is used.
+pub struct SymbolicColors<'a> {
+    image: &'a BackgroundImage,
+    text_region: &'a Region,
+    image_colors: OnceCell<ImageColors>,
+    text_colors: OnceCell<ImageColors>,
+}
+
+impl<'a> SymbolicColors<'a> {
+    pub fn new(image: &'a BackgroundImage, text_region: &'a Region) -> Self {
+        Self {
+            image,
+            text_region,
+            image_colors: OnceCell::new(),
+            text_colors: OnceCell::new(),
+        }
+    }
+
+    /// Parses a color, where the symbolic colors are those of the whole background.
+    pub fn parse(&self, raw_color: &str) -> Result<[u8; 4]> {
+        if !is_symbolic_color(raw_color) {
+            return parse_color(raw_color);
+        }
+        self.image_colors
+            .get_or_init(|| ImageColors::extract(self.image, None))
+            .resolve(raw_color)
+    }
+
+    /// Parses a text color, where the symbolic colors are those of the text
+    /// region, lightened or darkened until readable on its dominant color.
+    pub fn parse_text_color(&self, raw_color: &str) -> Result<[u8; 4]> {
+        if !is_symbolic_color(raw_color) {
+            return parse_color(raw_color);
+        }
+        let colors = self
+            .text_colors
+            .get_or_init(|| ImageColors::extract(self.image, Some(self.text_region)));
+        let [red, green, blue, alpha] = colors.resolve(raw_color)?;
+        let [red, green, blue] = ensure_contrast(
+            Lab::from_rgb([red, green, blue]),
+            Lab::from_rgb(colors.dominant),
+            TEXT_CONTRAST,
+        )
+        .to_rgb();
+
+        Ok([red, green, blue, alpha])
+    }
+}
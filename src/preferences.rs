@@ -0,0 +1,171 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::history::{load_history, HistoryEntry};
+use crate::source::random_color;
+
+/// Number of random colors weighed against each other when picking a color.
+const COLOR_CANDIDATES: usize = 8;
+/// Colors with a chroma below this (out of 1) are grays, which have no hue.
+const GRAY_CHROMA: f64 = 0.1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rating {
+    Like,
+    Dislike,
+}
+
+/// Random choices made while generating a wallpaper, which ratings steer.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Choices {
+    /// Unsplash photo of the background
+    #[serde(default)]
+    pub image_id: Option<String>,
+    /// Image the background was made from
+    #[serde(default)]
+    pub image_url: Option<String>,
+    #[serde(default)]
+    pub image_credit: Option<PhotoCredit>,
+    /// Colors of a color or gradient background, as RGB
+    #[serde(default)]
+    pub colors: Vec<[u8; 3]>,
+    #[serde(default)]
+    pub quote_author: Option<String>,
+}
+
+/// Photographer of an Unsplash photo, whose guidelines ask to credit them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PhotoCredit {
+    pub name: String,
+    pub profile_url: String,
+}
+
+/// Choices of the liked and disliked wallpapers, weighing the next random choices.
+///
+/// Fonts and terminal palettes are not weighed: the font comes from the options
+/// and the palette from the wallpaper, neither is a random choice.
+#[derive(Debug, Clone, Default)]
+pub struct Preferences {
+    liked: Vec<Choices>,
+    disliked: Vec<Choices>,
+}
+
+impl Preferences {
+    /// Preferences from the ratings of the wallpaper history. An unreadable
+    /// history has no preferences.
+    pub fn load() -> Self {
+        match load_history() {
+            Ok(entries) => Self::from_history(&entries),
+            Err(e) => {
+                eprintln!("{e}. Ignoring the wallpaper ratings.");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn from_history(entries: &[HistoryEntry]) -> Self {
+        let rated = |rating: Rating| {
+            entries
+                .iter()
+                .filter(|entry| entry.rating == Some(rating))
+                .map(|entry| entry.choices.clone())
+                .collect()
+        };
+
+        Self {
+            liked: rated(Rating::Like),
+            disliked: rated(Rating::Dislike),
+        }
+    }
+
+    /// Unsplash photos of disliked wallpapers are never shown again.
+    pub fn is_disliked_image(&self, image_id: &str) -> bool {
+        self.disliked
+            .iter()
+            .any(|choices| choices.image_id.as_deref() == Some(image_id))
+    }
+
+    /// Relative chance of picking a quote by `author`.
+    pub fn author_weight(&self, author: &str) -> f64 {
+        let count = |rated: &[Choices]| {
+            rated
+                .iter()
+                .filter(|choices| choices.quote_author.as_deref() == Some(author))
+                .count() as f64
+        };
+
+        (1.0 + 2.0 * count(&self.liked)) / (1.0 + 4.0 * count(&self.disliked))
+    }
+
+    /// Relative chance of picking a color, higher for hues close to the liked
+    /// colors and lower for hues close to the disliked ones.
+    pub fn color_weight(&self, color: [u8; 3]) -> f64 {
+        let similarity = |rated: &[Choices]| {
+            let similarities: Vec<f64> = rated
+                .iter()
+                .flat_map(|choices| &choices.colors)
+                .map(|rated_color| hue_similarity(color, *rated_color))
+                .collect();
+            if similarities.is_empty() {
+                0.0
+            } else {
+                similarities.iter().sum::<f64>() / similarities.len() as f64
+            }
+        };
+
+        (1.0 + 3.0 * similarity(&self.liked)) * (1.0 - 0.9 * similarity(&self.disliked))
+    }
+
+    /// Random color, drawn toward the preferred hues.
+    pub fn random_color(&self, rng: &mut impl Rng) -> [u8; 3] {
+        let candidates: Vec<[u8; 3]> = (0..COLOR_CANDIDATES)
+            .map(|_| {
+                let [red, green, blue, _] = random_color(rng).0;
+                [red, green, blue]
+            })
+            .collect();
+
+        *candidates
+            .choose_weighted(rng, |color| self.color_weight(*color))
+            .unwrap_or(&candidates[0])
+    }
+}
+
+/// Hue in degrees, [None] for grays.
+fn hue([red, green, blue]: [u8; 3]) -> Option<f64> {
+    let (red, green, blue) = (
+        red as f64 / 255.0,
+        green as f64 / 255.0,
+        blue as f64 / 255.0,
+    );
+    let max = red.max(green).max(blue);
+    let chroma = max - red.min(green).min(blue);
+    if chroma < GRAY_CHROMA {
+        return None;
+    }
+
+    let sector = if max == red {
+        ((green - blue) / chroma).rem_euclid(6.0)
+    } else if max == green {
+        (blue - red) / chroma + 2.0
+    } else {
+        (red - green) / chroma + 4.0
+    };
+    Some(sector * 60.0)
+}
+
+/// From 0 for hues more than 60° apart to 1 for the same hue. Grays are only
+/// similar to other grays.
+fn hue_similarity(a: [u8; 3], b: [u8; 3]) -> f64 {
+    match (hue(a), hue(b)) {
+        (Some(a), Some(b)) => {
+            let distance = (a - b).abs();
+            let distance = distance.min(360.0 - distance);
+            (1.0 - distance / 60.0).max(0.0)
+        }
+        (None, None) => 1.0,
+        _ => 0.0,
+    }
+}
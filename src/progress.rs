@@ -0,0 +1,170 @@
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime, NaiveTime};
+use glyph_brush_layout::{BuiltInLineBreaker, HorizontalAlign, Layout, VerticalAlign};
+use image::Rgba;
+
+use crate::args::{Countdown, CountdownDate, ProgressOptions, ProgressPeriod};
+use crate::calendar::anchor_position;
+use crate::display::Region;
+use crate::text::{draw_textbox, TextBox, TextConfig, TextSize};
+use crate::BackgroundImage;
+
+/// Width of the countdowns and progress bars, in rows.
+const WIDTH_ROWS: u32 = 10;
+
+/// Number of days until the date of a countdown, negative once it has passed.
+/// Yearly dates are counted until their next occurrence.
+pub fn days_until(date: CountdownDate, today: NaiveDate) -> i64 {
+    let date = match date {
+        CountdownDate::Once(date) => date,
+        CountdownDate::Yearly { month, day } => (today.year()..today.year() + 8)
+            .filter_map(|year| NaiveDate::from_ymd_opt(year, month, day))
+            .find(|date| *date >= today)
+            .unwrap_or(today),
+    };
+
+    (date - today).num_days()
+}
+
+/// Text of a countdown, such as "12 days until Christmas".
+pub fn countdown_text(countdown: &Countdown, today: NaiveDate) -> String {
+    let label = match (&countdown.label, countdown.date) {
+        (Some(label), _) => label.clone(),
+        (None, CountdownDate::Once(date)) => date.format("%-d %B %Y").to_string(),
+        (None, CountdownDate::Yearly { month, day }) => NaiveDate::from_ymd_opt(2000, month, day)
+            .map(|date| date.format("%-d %B").to_string())
+            .unwrap_or_default(),
+    };
+
+    match days_until(countdown.date, today) {
+        0 => format!("{label} is today"),
+        1 => format!("1 day until {label}"),
+        -1 => format!("1 day since {label}"),
+        days if days < 0 => format!("{} days since {label}", -days),
+        days => format!("{days} days until {label}"),
+    }
+}
+
+/// Start and end of the period containing `now`.
+fn period_bounds(period: ProgressPeriod, now: NaiveDateTime) -> (NaiveDateTime, NaiveDateTime) {
+    let today = now.date();
+    let (start, months) = match period {
+        ProgressPeriod::Year => (today.with_ordinal(1), 12),
+        ProgressPeriod::Quarter => (
+            NaiveDate::from_ymd_opt(today.year(), (today.month0() / 3) * 3 + 1, 1),
+            3,
+        ),
+        ProgressPeriod::Month => (today.with_day(1), 1),
+        ProgressPeriod::Day => {
+            let start = today.and_time(NaiveTime::MIN);
+            return (start, start + chrono::Duration::days(1));
+        }
+    };
+
+    let start = start.unwrap_or(today).and_time(NaiveTime::MIN);
+    let end = start
+        .checked_add_months(Months::new(months))
+        .unwrap_or(start + chrono::Duration::days(1));
+    (start, end)
+}
+
+/// Elapsed part of the period containing `now`, from 0 to 1.
+pub fn period_progress(period: ProgressPeriod, now: NaiveDateTime) -> f32 {
+    let (start, end) = period_bounds(period, now);
+    let elapsed = (now - start).num_seconds() as f32;
+    let length = (end - start).num_seconds().max(1) as f32;
+
+    (elapsed / length).clamp(0.0, 1.0)
+}
+
+/// Name of the period containing `now`, such as "Q4 2026".
+fn period_name(period: ProgressPeriod, now: NaiveDateTime) -> String {
+    match period {
+        ProgressPeriod::Year => now.format("%Y").to_string(),
+        ProgressPeriod::Quarter => format!("Q{} {}", now.month0() / 3 + 1, now.year()),
+        ProgressPeriod::Month => now.format("%B").to_string(),
+        ProgressPeriod::Day => now.format("%A").to_string(),
+    }
+}
+
+/// Draws the countdowns, then a progress bar with rounded ends for every period,
+/// anchored in `region`.
+pub fn draw_progress(
+    image: &mut BackgroundImage,
+    region: &Region,
+    options: &ProgressOptions,
+    now: NaiveDateTime,
+    style: &TextConfig,
+) {
+    let row = (region.height / 22).max(8);
+    let bar_height = (row * 3 / 10).max(2);
+    let bar_row = row + bar_height + row / 2;
+    let width = WIDTH_ROWS * row;
+    let height = options.countdowns.len() as u32 * row + options.periods.len() as u32 * bar_row;
+    let (left, mut top) = anchor_position(region, options.position, width, height);
+
+    let color = style.color;
+    let track_color = Rgba([color.0[0], color.0[1], color.0[2], color.0[3] / 4]);
+    let aligned = |h_align: HorizontalAlign| TextConfig {
+        size: TextSize::PxScale(row as f32 * 0.45),
+        layout: Layout::Wrap {
+            h_align,
+            v_align: VerticalAlign::Center,
+            line_breaker: BuiltInLineBreaker::UnicodeLineBreaker,
+        },
+        ..style.clone()
+    };
+    let text =
+        |image: &mut BackgroundImage, text: String, h_align: HorizontalAlign, x: u32, y: u32| {
+            let textbox = TextBox {
+                text,
+                width,
+                height: row,
+                style: aligned(h_align),
+            };
+            draw_textbox(image, textbox, x, y);
+        };
+
+    for countdown in &options.countdowns {
+        text(
+            image,
+            countdown_text(countdown, now.date()),
+            HorizontalAlign::Left,
+            left,
+            top + row / 2,
+        );
+        top += row;
+    }
+
+    for &period in &options.periods {
+        let progress = period_progress(period, now);
+        text(
+            image,
+            period_name(period, now),
+            HorizontalAlign::Left,
+            left,
+            top + row / 2,
+        );
+        text(
+            image,
+            format!("{}%", (progress * 100.0).floor()),
+            HorizontalAlign::Right,
+            left + width,
+            top + row / 2,
+        );
+
+        let mut bar = Region {
+            x: left,
+            y: top + row,
+            width,
+            height: bar_height,
+        };
+        let radius = bar_height as f32 / 2.0;
+        image.fill_rounded_rect(&bar, radius, &track_color);
+        if progress > 0.0 {
+            // Never narrower than its height, to keep both ends rounded
+            bar.width = ((width as f32 * progress) as u32).max(bar_height);
+            image.fill_rounded_rect(&bar, radius, &color);
+        }
+        top += bar_row;
+    }
+}
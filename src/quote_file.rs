@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::args::{QuoteFileFormat, QuoteOptions, QuoteOrder};
+use crate::error::{Error, Result};
+use crate::quotes::Quote;
+use crate::utils::application_data_path;
+
+/// Separator between the clippings of a Kindle "My Clippings.txt" file.
+const KINDLE_SEPARATOR: &str = "==========";
+
+/// Reads the quotes of a file.
+pub fn read_quote_file(path: &Path, format: QuoteFileFormat) -> Result<Vec<Quote>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("Failed to read quote file {}. {e}", path.display())))?;
+    // Kindle clippings start with a byte order mark
+    let contents = contents.trim_start_matches('\u{feff}');
+
+    let format = match format {
+        QuoteFileFormat::Auto => detect_format(path, contents),
+        format => format,
+    };
+    let quotes = match format {
+        QuoteFileFormat::Csv => parse_csv(contents)?,
+        QuoteFileFormat::Json => serde_json::from_str(contents)
+            .map_err(|e| Error::Config(format!("Invalid quote file {}. {e}", path.display())))?,
+        QuoteFileFormat::Kindle => parse_kindle_clippings(contents),
+        QuoteFileFormat::Fortune | QuoteFileFormat::Auto => parse_fortune(contents),
+    };
+
+    if quotes.is_empty() {
+        return Err(Error::Config(format!(
+            "No quotes found in {}",
+            path.display()
+        )));
+    }
+    Ok(quotes)
+}
+
+/// Guesses the format of a quote file from its extension and contents.
+fn detect_format(path: &Path, contents: &str) -> QuoteFileFormat {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("csv") => QuoteFileFormat::Csv,
+        Some("json") => QuoteFileFormat::Json,
+        _ if contents.lines().any(|line| line.trim() == KINDLE_SEPARATOR) => {
+            QuoteFileFormat::Kindle
+        }
+        _ => QuoteFileFormat::Fortune,
+    }
+}
+
+/// Parses a BSD fortune file, where quotes are separated by lines holding a
+/// single "%". A last line starting with "--" is the author.
+fn parse_fortune(contents: &str) -> Vec<Quote> {
+    let mut quotes = vec![];
+    let mut lines: Vec<&str> = vec![];
+    for line in contents.lines().chain(std::iter::once("%")) {
+        if line.trim() != "%" {
+            lines.push(line);
+            continue;
+        }
+
+        let mut author = String::new();
+        if let Some(last) = lines.last() {
+            if let Some(name) = last.trim().strip_prefix("--") {
+                author = name.trim().to_string();
+                lines.pop();
+            }
+        }
+        let text = lines.join("\n").trim().to_string();
+        if !text.is_empty() {
+            quotes.push(Quote {
+                text,
+                author,
+                tags: vec![],
+                source: None,
+            });
+        }
+        lines.clear();
+    }
+
+    quotes
+}
+
+/// Parses a CSV file with a header. The quote is read from the "text" or "quote"
+/// column, and the optional "author", "source" and "tags" columns. Tags are
+/// separated by ";".
+fn parse_csv(contents: &str) -> Result<Vec<Quote>> {
+    let mut reader = csv::Reader::from_reader(contents.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| Error::Config(format!("Invalid CSV quote file. {e}")))?
+        .iter()
+        .map(|header| header.trim().to_lowercase())
+        .collect();
+    let column = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|header| names.contains(&header.as_str()))
+    };
+
+    let text_column = column(&["text", "quote"]).ok_or_else(|| {
+        Error::Config("CSV quote file is missing a \"text\" or \"quote\" column".to_string())
+    })?;
+    let author_column = column(&["author"]);
+    let source_column = column(&["source", "book", "title"]);
+    let tags_column = column(&["tags", "tag"]);
+
+    let mut quotes = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|e| Error::Config(format!("Invalid CSV quote file. {e}")))?;
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        let Some(text) = field(Some(text_column)) else {
+            continue;
+        };
+        quotes.push(Quote {
+            text,
+            author: field(author_column).unwrap_or_default(),
+            tags: field(tags_column)
+                .map(|tags| {
+                    tags.split(';')
+                        .map(|tag| tag.trim().to_lowercase())
+                        .filter(|tag| !tag.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            source: field(source_column),
+        });
+    }
+
+    Ok(quotes)
+}
+
+/// Parses the highlights of a Kindle "My Clippings.txt" file. Each clipping is:
+///
+/// ```text
+/// <title> (<author>)
+/// - Your Highlight on page 12 | Location 180-182 | Added on ...
+///
+/// <highlight>
+/// ==========
+/// ```
+///
+/// Notes and bookmarks are skipped.
+fn parse_kindle_clippings(contents: &str) -> Vec<Quote> {
+    contents
+        .split(KINDLE_SEPARATOR)
+        .filter_map(|clipping| {
+            let mut lines = clipping
+                .lines()
+                .map(str::trim)
+                .skip_while(|line| line.is_empty());
+            let heading = lines.next()?.trim_start_matches('\u{feff}');
+            let details = lines.next()?;
+            if !details.contains("Highlight") {
+                return None;
+            }
+
+            let text = lines.collect::<Vec<&str>>().join("\n").trim().to_string();
+            if text.is_empty() {
+                return None;
+            }
+
+            // The author is in the last parentheses, titles may contain parentheses too
+            let (title, author) = match heading.rfind('(') {
+                Some(start) if heading.ends_with(')') => (
+                    heading[..start].trim(),
+                    heading[start + 1..heading.len() - 1].trim(),
+                ),
+                _ => (heading, ""),
+            };
+
+            Some(Quote {
+                text,
+                author: author.to_string(),
+                tags: vec![],
+                source: Some(title.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Quotes previously selected from a quote file.
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct SelectionState {
+    /// Index of the next quote in sequential order
+    next: usize,
+    /// Texts of the most recently selected quotes, oldest first
+    recent: Vec<String>,
+}
+
+/// Path of the file holding the selection state of every quote file.
+fn state_path() -> Result<PathBuf> {
+    let mut path = application_data_path()?;
+    path.push("quote_files.json");
+
+    Ok(path)
+}
+
+fn load_states() -> Result<HashMap<String, SelectionState>> {
+    let path = state_path()?;
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Selects a quote from a quote file in the given order, remembering the
+/// selection for the next time the file is used.
+pub fn select_quote(
+    path: &Path,
+    format: QuoteFileFormat,
+    order: QuoteOrder,
+    filters: &QuoteOptions,
+    rng: &mut impl Rng,
+) -> Result<Quote> {
+    let quotes: Vec<Quote> = read_quote_file(path, format)?
+        .into_iter()
+        .filter(|quote| filters.matches(quote))
+        .collect();
+    if quotes.is_empty() {
+        return Err(Error::Config(
+            "No quote matches the quote filters".to_string(),
+        ));
+    }
+
+    let key = std::fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .to_string();
+    let mut states = load_states().unwrap_or_else(|e| {
+        eprintln!("{e}. Resetting the quote file selections.");
+        HashMap::new()
+    });
+    let state = states.entry(key).or_default();
+
+    let quote = match order {
+        QuoteOrder::Random => quotes.choose(rng),
+        QuoteOrder::Sequential => {
+            let index = state.next % quotes.len();
+            state.next = index + 1;
+            quotes.get(index)
+        }
+        QuoteOrder::NoRepeat(count) => {
+            // Always leave at least one quote to pick from
+            let count = count.min(quotes.len() - 1);
+            let recent = &state.recent[state.recent.len().saturating_sub(count)..];
+            let unseen: Vec<&Quote> = quotes
+                .iter()
+                .filter(|quote| !recent.contains(&quote.text))
+                .collect();
+            match unseen.choose(rng) {
+                Some(quote) => Some(*quote),
+                None => quotes.choose(rng),
+            }
+        }
+    }
+    .cloned()
+    .ok_or_else(|| Error::Config("No quote matches the quote filters".to_string()))?;
+
+    if let QuoteOrder::NoRepeat(count) = order {
+        state.recent.push(quote.text.clone());
+        let excess = state.recent.len().saturating_sub(count);
+        state.recent.drain(..excess);
+    }
+    std::fs::write(state_path()?, serde_json::to_string_pretty(&states)?)?;
+
+    Ok(quote)
+}
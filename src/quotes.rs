@@ -0,0 +1,129 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::args::QuoteOptions;
+use crate::error::{Error, Result};
+use crate::preferences::Preferences;
+use crate::utils::application_data_path;
+
+/// Quotes embedded in the binary, available without a network connection.
+const BUNDLED_QUOTES: &str = include_str!("../assets/quotes/quotes.json");
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Quote {
+    #[serde(alias = "quote")]
+    pub text: String,
+    #[serde(default)]
+    pub author: String,
+    /// Lowercase topics of the quote, e.g. "stoicism"
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Book or work the quote is taken from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl Quote {
+    /// Number of characters in the quote, excluding the author.
+    pub fn length(&self) -> usize {
+        self.text.chars().count()
+    }
+}
+
+impl QuoteOptions {
+    /// Whether a quote passes every filter that is set.
+    pub fn matches(&self, quote: &Quote) -> bool {
+        let tag = self.tag.as_ref().is_none_or(|tag| {
+            quote
+                .tags
+                .iter()
+                .any(|quote_tag| quote_tag.eq_ignore_ascii_case(tag))
+        });
+        let author = self
+            .author
+            .as_ref()
+            .is_none_or(|author| quote.author.to_lowercase().contains(&author.to_lowercase()));
+        let length = self
+            .max_length
+            .is_none_or(|max_length| quote.length() <= max_length);
+
+        tag && author && length
+    }
+}
+
+/// Path of the file quotes fetched from the network are cached in.
+fn cache_path() -> Result<PathBuf> {
+    let mut path = application_data_path()?;
+    path.push("quote_cache.json");
+
+    Ok(path)
+}
+
+pub fn bundled_quotes() -> Result<Vec<Quote>> {
+    serde_json::from_str(BUNDLED_QUOTES)
+        .map_err(|e| Error::Config(format!("Invalid bundled quotes. {e}")))
+}
+
+/// Quotes previously fetched from the network. Empty if none were cached yet.
+pub fn cached_quotes() -> Result<Vec<Quote>> {
+    let path = cache_path()?;
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|e| Error::Config(format!("Invalid quote cache at {}. {e}", path.display()))),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Adds a quote to the cache, unless a quote with the same text is already cached.
+pub fn cache_quote(quote: &Quote) -> Result<()> {
+    let mut quotes = cached_quotes()?;
+    if quotes.iter().any(|cached| cached.text == quote.text) {
+        return Ok(());
+    }
+    quotes.push(quote.clone());
+    std::fs::write(cache_path()?, serde_json::to_string_pretty(&quotes)?)?;
+
+    Ok(())
+}
+
+/// Bundled and cached quotes. An unreadable cache is skipped with a warning.
+pub fn local_quotes() -> Result<Vec<Quote>> {
+    let mut quotes = bundled_quotes()?;
+    match cached_quotes() {
+        Ok(cached) => {
+            for quote in cached {
+                if !quotes.iter().any(|local| local.text == quote.text) {
+                    quotes.push(quote);
+                }
+            }
+        }
+        Err(e) => eprintln!("{e}. Ignoring the quote cache."),
+    }
+
+    Ok(quotes)
+}
+
+/// Picks a random quote among those matching the filters, weighted by the
+/// ratings of their authors.
+pub fn random_quote(
+    quotes: &[Quote],
+    filters: &QuoteOptions,
+    preferences: &Preferences,
+    rng: &mut impl Rng,
+) -> Result<Quote> {
+    let matching: Vec<&Quote> = quotes
+        .iter()
+        .filter(|quote| filters.matches(quote))
+        .collect();
+
+    matching
+        .choose_weighted(rng, |quote| preferences.author_weight(&quote.author))
+        .ok()
+        .map(|quote| (*quote).clone())
+        .ok_or_else(|| Error::Config("No quote matches the quote filters".to_string()))
+}
@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::preferences::PhotoCredit;
+use crate::utils::{run_command_with_env, shell_quote};
+
+/// Time a post hook may run for before it is killed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Summary of a generated wallpaper, printed by --json and passed to the post hooks.
+#[derive(Serialize, Debug, Clone)]
+pub struct RunReport {
+    /// Path of the saved wallpaper, "-" for stdout
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    /// Lines of text drawn on the wallpaper
+    pub text: Vec<String>,
+    pub quote: Option<String>,
+    pub author: Option<String>,
+    /// Colors of a color or gradient background, as hex
+    pub background_colors: Vec<String>,
+    pub text_color: String,
+    /// Image the background was made from
+    pub source_url: Option<String>,
+    pub photo_credit: Option<PhotoCredit>,
+    /// Seed of the random choices, reproducing them with --seed
+    pub seed: u64,
+    pub timings: Timings,
+}
+
+/// Time spent on each step of the run, in milliseconds.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Timings {
+    /// Creating the background, which clock redraws of the daemon reuse
+    pub background_ms: u64,
+    pub text_ms: u64,
+    /// Drawing the shapes, overlays, text, calendar and progress bars
+    pub draw_ms: u64,
+    pub save_ms: u64,
+    pub set_ms: u64,
+    pub hooks_ms: u64,
+}
+
+/// Milliseconds elapsed since `start`, restarting it for the next step.
+pub fn lap(start: &mut Instant) -> u64 {
+    let elapsed = start.elapsed().as_millis() as u64;
+    *start = Instant::now();
+    elapsed
+}
+
+pub fn hex([red, green, blue]: [u8; 3]) -> String {
+    format!("#{red:02x}{green:02x}{blue:02x}")
+}
+
+impl RunReport {
+    /// Environment variables describing the wallpaper to its post hooks.
+    fn hook_env(&self) -> Vec<(String, String)> {
+        let mut env = vec![
+            ("MACGROUND_PATH", self.path.display().to_string()),
+            ("MACGROUND_WIDTH", self.width.to_string()),
+            ("MACGROUND_HEIGHT", self.height.to_string()),
+            ("MACGROUND_TEXT", self.text.join("\n")),
+            ("MACGROUND_TEXT_COLOR", self.text_color.clone()),
+            (
+                "MACGROUND_BACKGROUND_COLORS",
+                self.background_colors.join(" "),
+            ),
+            ("MACGROUND_SEED", self.seed.to_string()),
+        ];
+        let optional = [
+            ("MACGROUND_QUOTE", self.quote.clone()),
+            ("MACGROUND_AUTHOR", self.author.clone()),
+            ("MACGROUND_SOURCE_URL", self.source_url.clone()),
+            (
+                "MACGROUND_PHOTOGRAPHER",
+                self.photo_credit.as_ref().map(|credit| credit.name.clone()),
+            ),
+            (
+                "MACGROUND_PHOTOGRAPHER_URL",
+                self.photo_credit
+                    .as_ref()
+                    .map(|credit| credit.profile_url.clone()),
+            ),
+        ];
+        env.extend(
+            optional
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, value?))),
+        );
+
+        env.into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect()
+    }
+}
+
+/// Runs the post hooks one after the other, where "{path}" is replaced by the
+/// path of the wallpaper. A failing hook is reported without stopping the others.
+pub fn run_hooks(hooks: &[String], report: &RunReport) {
+    let env = report.hook_env();
+    let path = shell_quote(&report.path.to_string_lossy());
+    for hook in hooks {
+        let command = hook.replace("{path}", &path);
+        match run_command_with_env(&command, &env, HOOK_TIMEOUT) {
+            Ok(output) if !output.is_empty() => eprintln!("{output}"),
+            Ok(_) => {}
+            Err(e) => eprintln!("Post hook failed. {e}"),
+        }
+    }
+}
@@ -0,0 +1,711 @@
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration as StdDuration;
+
+use chrono::{Datelike, Duration, Local, NaiveDateTime, NaiveTime, Weekday};
+use serde::Deserialize;
+
+use crate::args::ScheduleOptions;
+use crate::cron::{CronExpression, SEARCH_LIMIT};
+use crate::daemon::{log, retry_delay, Signals};
+use crate::error::{Error, Result};
+use crate::layout::load_layout;
+use crate::sun::{local_sun_event, Location, SunEvent};
+use crate::update_wallpaper;
+use crate::utils::application_data_path;
+
+/// Days searched for a sun event, which may not happen every day near the poles.
+const SUN_SEARCH_DAYS: i64 = 7;
+/// How long the scheduler waits before checking again when nothing is planned.
+const IDLE_WAIT: StdDuration = StdDuration::from_secs(60 * 60);
+/// Most boundaries skipped while searching for the next or last change of layout.
+const MAX_BOUNDARIES: usize = 10_000;
+
+/// Source of the current (local) time.
+///
+/// The scheduler never reads the system time directly, so schedules can be
+/// evaluated against any clock.
+pub trait Clock {
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// [Clock] reading the local system time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+/// Entry of a schedule file, as written by the user.
+///
+/// ```json
+/// [
+///     { "days": ["mon", "tue", "wed", "thu", "fri"], "from": "09:00", "to": "18:00", "layout": "work" },
+///     { "from": "18:00", "to": "23:00", "layout": "calm" },
+///     { "cron": "0 * * * sat,sun", "layout": "weekend" },
+///     { "sun": "sunset", "layout": "evening" }
+/// ]
+/// ```
+#[derive(Deserialize)]
+struct RawScheduleEntry {
+    layout: String,
+    cron: Option<String>,
+    sun: Option<SunEvent>,
+    days: Option<Vec<String>>,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+enum Trigger {
+    /// The layout is applied at every minute matching the expression.
+    Cron(CronExpression),
+    /// The layout is applied every day when the sun event happens at the location.
+    Sun(SunEvent, Location),
+    /// The layout is applied at `from` and stays active until `to`. A range
+    /// ending before it starts runs past midnight. `days` (every day if empty)
+    /// restricts the days the range starts on.
+    TimeRange {
+        days: Vec<Weekday>,
+        from: NaiveTime,
+        to: NaiveTime,
+    },
+}
+
+struct ScheduleEntry {
+    layout: String,
+    trigger: Trigger,
+}
+
+/// A planned change of layout.
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub time: NaiveDateTime,
+    pub layout: String,
+}
+
+/// Maps cron expressions and time ranges to saved layouts. When several entries
+/// apply, the most recently triggered one wins, and ties go to the entry listed first.
+pub struct Schedule {
+    entries: Vec<ScheduleEntry>,
+}
+
+impl Schedule {
+    /// Loads a schedule file. `location` is required by entries following the sun.
+    pub fn load(path: &Path, location: Option<Location>) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read schedule at {}. {e}", path.display()))?;
+
+        Self::parse(&contents, location)
+    }
+
+    /// Parses the JSON contents of a schedule file.
+    fn parse(contents: &str, location: Option<Location>) -> Result<Self> {
+        let raw_entries: Vec<RawScheduleEntry> = serde_json::from_str(contents)?;
+
+        let entries = raw_entries
+            .into_iter()
+            .map(|raw_entry| ScheduleEntry::new(raw_entry, location))
+            .collect::<Result<_>>()?;
+
+        Ok(Self { entries })
+    }
+
+    /// Layout that should be displayed at `time`, which is the layout of the last
+    /// change at or before it, along with the time of that change.
+    pub fn active_at(&self, time: &NaiveDateTime) -> Option<(NaiveDateTime, &str)> {
+        let mut cursor = *time;
+        for _ in 0..MAX_BOUNDARIES {
+            let boundary = self
+                .entries
+                .iter()
+                .filter_map(|entry| entry.trigger.previous_boundary_at_or_before(&cursor))
+                .max()?;
+            if let Some(entry) = self.change_at(&boundary) {
+                return Some((boundary, entry.layout.as_str()));
+            }
+            cursor = boundary - Duration::seconds(1);
+        }
+
+        None
+    }
+
+    /// Entry in effect at `time` that was triggered last, along with the time it was triggered.
+    fn latest_entry_at(&self, time: &NaiveDateTime) -> Option<(NaiveDateTime, &ScheduleEntry)> {
+        let mut latest: Option<(NaiveDateTime, &ScheduleEntry)> = None;
+        for entry in &self.entries {
+            if let Some(triggered) = entry.trigger.last_triggered(time) {
+                if latest.is_none_or(|(latest, _)| triggered > latest) {
+                    latest = Some((triggered, entry));
+                }
+            }
+        }
+
+        latest
+    }
+
+    /// Entry whose layout is applied at `boundary`, if the layout changes then.
+    /// Re-triggering the displayed layout (e.g. a cron entry firing again) counts
+    /// as a change. When a time range ends, the layout only changes if another
+    /// time range is in effect.
+    fn change_at(&self, boundary: &NaiveDateTime) -> Option<&ScheduleEntry> {
+        let (triggered, entry) = self.latest_entry_at(boundary)?;
+        let previous = self
+            .latest_entry_at(&(*boundary - Duration::minutes(1)))
+            .map(|(_, entry)| entry.layout.as_str());
+        let resumed = matches!(entry.trigger, Trigger::TimeRange { .. })
+            && previous != Some(entry.layout.as_str());
+
+        (triggered == *boundary || resumed).then_some(entry)
+    }
+
+    /// First change of layout strictly after `time`, see [Schedule::change_at].
+    pub fn next_change(&self, time: &NaiveDateTime) -> Option<Change> {
+        let mut cursor = *time;
+        for _ in 0..MAX_BOUNDARIES {
+            let boundary = self
+                .entries
+                .iter()
+                .filter_map(|entry| entry.trigger.next_boundary_after(&cursor))
+                .min()?;
+            if let Some(entry) = self.change_at(&boundary) {
+                return Some(Change {
+                    time: boundary,
+                    layout: entry.layout.clone(),
+                });
+            }
+            cursor = boundary;
+        }
+
+        None
+    }
+
+    /// The next `count` changes of layout after `time`.
+    pub fn planned_changes(&self, time: &NaiveDateTime, count: usize) -> Vec<Change> {
+        let mut changes: Vec<Change> = vec![];
+        let mut cursor = *time;
+        while changes.len() < count {
+            match self.next_change(&cursor) {
+                Some(change) => {
+                    cursor = change.time;
+                    changes.push(change);
+                }
+                None => break,
+            }
+        }
+
+        changes
+    }
+}
+
+impl ScheduleEntry {
+    fn new(raw_entry: RawScheduleEntry, location: Option<Location>) -> Result<Self> {
+        let trigger = match (raw_entry.cron, raw_entry.sun, raw_entry.from, raw_entry.to) {
+            (Some(cron), None, None, None) => Trigger::Cron(CronExpression::from_str(&cron)?),
+            (None, Some(event), None, None) => {
+                let location = location.ok_or_else(|| {
+                    Error::Config(
+                        "Schedule entries following the sun require a location, set --latitude and --longitude"
+                            .to_string(),
+                    )
+                })?;
+                Trigger::Sun(event, location)
+            }
+            (None, None, Some(from), Some(to)) => {
+                let days = raw_entry
+                    .days
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|day| {
+                        Weekday::from_str(day)
+                            .map_err(|_| Error::Config(format!("Invalid day {day}")))
+                    })
+                    .collect::<Result<_>>()?;
+
+                Trigger::TimeRange {
+                    days,
+                    from: parse_time(&from)?,
+                    to: parse_time(&to)?,
+                }
+            }
+            _ => {
+                return Err(Error::Config(format!(
+                    "Schedule entry for layout \"{}\" needs one of \"cron\", \"sun\" or both \"from\" and \"to\"",
+                    raw_entry.layout
+                )))
+            }
+        };
+
+        Ok(Self {
+            layout: raw_entry.layout,
+            trigger,
+        })
+    }
+}
+
+impl Trigger {
+    /// Time the trigger last fired, if it is still in effect at `time`.
+    fn last_triggered(&self, time: &NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Trigger::Cron(expression) => expression.previous_at_or_before(time, SEARCH_LIMIT),
+            Trigger::Sun(event, location) => (-SUN_SEARCH_DAYS..=1)
+                .rev()
+                .filter_map(|offset| {
+                    local_sun_event(location, time.date() + Duration::days(offset), *event)
+                })
+                .find(|event_time| event_time <= time),
+            Trigger::TimeRange { .. } => self
+                .ranges_near(time)
+                .into_iter()
+                .find(|(start, end)| start <= time && time < end)
+                .map(|(start, _)| start),
+        }
+    }
+
+    /// First time strictly after `time` at which the trigger fires or stops being in effect.
+    fn next_boundary_after(&self, time: &NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Trigger::Cron(expression) => expression.next_after(time),
+            Trigger::Sun(event, location) => (-1..=SUN_SEARCH_DAYS)
+                .filter_map(|offset| {
+                    local_sun_event(location, time.date() + Duration::days(offset), *event)
+                })
+                .find(|event_time| event_time > time),
+            Trigger::TimeRange { .. } => self
+                .ranges_near(time)
+                .into_iter()
+                .flat_map(|(start, end)| [start, end])
+                .filter(|boundary| boundary > time)
+                .min(),
+        }
+    }
+
+    /// Last time at or before `time` at which the trigger fired or stopped being in effect.
+    fn previous_boundary_at_or_before(&self, time: &NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Trigger::Cron(expression) => expression.previous_at_or_before(time, SEARCH_LIMIT),
+            Trigger::Sun(..) => self.last_triggered(time),
+            Trigger::TimeRange { .. } => self
+                .ranges_before(time)
+                .into_iter()
+                .flat_map(|(start, end)| [start, end])
+                .filter(|boundary| boundary <= time)
+                .max(),
+        }
+    }
+
+    /// Start and end of the time ranges starting from the day before `time`
+    /// up to a week after it.
+    fn ranges_near(&self, time: &NaiveDateTime) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        self.ranges_starting(time, -1..=8)
+    }
+
+    /// Start and end of the time ranges starting from a week before `time` up
+    /// to the day after it.
+    fn ranges_before(&self, time: &NaiveDateTime) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        self.ranges_starting(time, -8..=1)
+    }
+
+    /// Start and end of the time ranges starting on the days `offsets` away from `time`.
+    fn ranges_starting(
+        &self,
+        time: &NaiveDateTime,
+        offsets: RangeInclusive<i64>,
+    ) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        let Trigger::TimeRange { days, from, to } = self else {
+            return vec![];
+        };
+
+        offsets
+            .map(|offset| time.date() + Duration::days(offset))
+            .filter(|date| days.is_empty() || days.contains(&date.weekday()))
+            .map(|date| {
+                let start = date.and_time(*from);
+                let end = if to > from {
+                    date.and_time(*to)
+                } else {
+                    (date + Duration::days(1)).and_time(*to)
+                };
+                (start, end)
+            })
+            .collect()
+    }
+}
+
+fn parse_time(raw_time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(raw_time, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(raw_time, "%H:%M:%S"))
+        .map_err(|_| Error::Config(format!("Invalid time {raw_time}, expected HH:MM")))
+}
+
+/// Default location of the schedule file.
+fn default_schedule_path() -> Result<PathBuf> {
+    let mut path = application_data_path()?;
+    path.push("schedule.json");
+
+    Ok(path)
+}
+
+/// Runs the scheduler, or prints the planned changes for `--dry-run`.
+pub fn run(
+    schedule_options: ScheduleOptions,
+    clock: &impl Clock,
+    location: Option<Location>,
+) -> Result<()> {
+    let path = match schedule_options.file.clone() {
+        Some(path) => path,
+        None => default_schedule_path()?,
+    };
+    let schedule = Schedule::load(&path, location)?;
+
+    if schedule_options.dry_run {
+        print_planned_changes(&schedule, clock, schedule_options.count);
+        return Ok(());
+    }
+
+    run_scheduler(schedule, &path, clock, location)
+}
+
+fn print_planned_changes(schedule: &Schedule, clock: &impl Clock, count: usize) {
+    for line in planned_changes_report(schedule, clock, count) {
+        println!("{line}");
+    }
+}
+
+/// Lines of --dry-run: the active layout, then the next `count` changes.
+fn planned_changes_report(schedule: &Schedule, clock: &impl Clock, count: usize) -> Vec<String> {
+    let now = clock.now();
+    let mut lines = vec![match schedule.active_at(&now) {
+        Some((triggered, layout)) => format!(
+            "Active: {layout} (since {})",
+            triggered.format("%a %Y-%m-%d %H:%M")
+        ),
+        None => "Active: none".to_string(),
+    }];
+
+    lines.extend(schedule.planned_changes(&now, count).iter().map(|change| {
+        format!(
+            "{}  {}",
+            change.time.format("%a %Y-%m-%d %H:%M"),
+            change.layout
+        )
+    }));
+    lines
+}
+
+/// Applies the layouts of the schedule as they become active until SIGTERM or
+/// SIGINT is received. SIGHUP reloads the schedule file.
+fn run_scheduler(
+    mut schedule: Schedule,
+    path: &Path,
+    clock: &impl Clock,
+    location: Option<Location>,
+) -> Result<()> {
+    let signals = Signals::register()?;
+    let active_change = |schedule: &Schedule| {
+        let now = clock.now();
+        schedule.active_at(&now).map(|(_, layout)| Change {
+            time: now,
+            layout: layout.to_string(),
+        })
+    };
+
+    log(&format!("Started schedule {}.", path.display()));
+
+    let mut next = active_change(&schedule);
+    let mut failures = 0;
+    while !signals.terminated() {
+        if signals.take_reload() {
+            match Schedule::load(path, location) {
+                Ok(reloaded) => {
+                    schedule = reloaded;
+                    next = active_change(&schedule);
+                    failures = 0;
+                    log("Reloaded schedule.");
+                }
+                Err(e) => log(&format!(
+                    "Failed to reload schedule, keeping the previous one. {e}"
+                )),
+            }
+        }
+
+        let now = clock.now();
+        let Some(change) = next.clone().or_else(|| schedule.next_change(&now)) else {
+            signals.wait(IDLE_WAIT);
+            continue;
+        };
+        if change.time > now {
+            next = Some(change.clone());
+            signals.wait((change.time - now).to_std().unwrap_or_default());
+            continue;
+        }
+
+        let result =
+            load_layout(&change.layout).and_then(|options| update_wallpaper(&options, None));
+        next = match result {
+            Ok(report) => {
+                failures = 0;
+                log(&format!(
+                    "Applied layout \"{}\", updated wallpaper to {}.",
+                    change.layout,
+                    report.path.display()
+                ));
+                None
+            }
+            Err(e) => {
+                failures += 1;
+                let until_next_change = schedule
+                    .next_change(&now)
+                    .and_then(|upcoming| (upcoming.time - now).to_std().ok())
+                    .unwrap_or(IDLE_WAIT);
+                let delay = retry_delay(failures, until_next_change);
+                log(&format!(
+                    "Failed to apply layout \"{}\", retrying in {}s. {e}",
+                    change.layout,
+                    delay.as_secs()
+                ));
+                Some(Change {
+                    time: now + Duration::from_std(delay).unwrap_or_default(),
+                    layout: change.layout,
+                })
+            }
+        };
+    }
+
+    log("Stopped.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    /// [Clock] stopped at a fixed time.
+    struct FixedClock(NaiveDateTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> NaiveDateTime {
+            self.0
+        }
+    }
+
+    const PARIS: Location = Location {
+        latitude: 48.86,
+        longitude: 2.35,
+    };
+
+    fn time(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn schedule(json: &str) -> Schedule {
+        Schedule::parse(json, Some(PARIS)).unwrap()
+    }
+
+    fn changes(
+        schedule: &Schedule,
+        time: &NaiveDateTime,
+        count: usize,
+    ) -> Vec<(NaiveDateTime, String)> {
+        schedule
+            .planned_changes(time, count)
+            .into_iter()
+            .map(|change| (change.time, change.layout))
+            .collect()
+    }
+
+    #[test]
+    fn cron_entries() {
+        let schedule = schedule(
+            r#"[
+                { "cron": "0 9 * * *", "layout": "morning" },
+                { "cron": "0 18 * * *", "layout": "evening" }
+            ]"#,
+        );
+        let now = time(2024, 6, 3, 12, 0);
+
+        assert_eq!(
+            schedule.active_at(&now),
+            Some((time(2024, 6, 3, 9, 0), "morning"))
+        );
+        let next = schedule.next_change(&now).unwrap();
+        assert_eq!(
+            (next.time, next.layout.as_str()),
+            (time(2024, 6, 3, 18, 0), "evening")
+        );
+        assert_eq!(
+            changes(&schedule, &now, 3),
+            [
+                (time(2024, 6, 3, 18, 0), "evening".to_string()),
+                (time(2024, 6, 4, 9, 0), "morning".to_string()),
+                (time(2024, 6, 4, 18, 0), "evening".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn cron_entry_firing_again_is_a_change() {
+        let schedule = schedule(r#"[{ "cron": "*/30 * * * *", "layout": "refresh" }]"#);
+        assert_eq!(
+            changes(&schedule, &time(2024, 6, 3, 23, 45), 2),
+            [
+                (time(2024, 6, 4, 0, 0), "refresh".to_string()),
+                (time(2024, 6, 4, 0, 30), "refresh".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn time_ranges_across_midnight() {
+        let schedule = schedule(
+            r#"[
+                { "from": "22:00", "to": "06:00", "layout": "night" },
+                { "from": "06:00", "to": "22:00", "layout": "day" }
+            ]"#,
+        );
+
+        assert_eq!(
+            schedule.active_at(&time(2024, 6, 4, 2, 0)),
+            Some((time(2024, 6, 3, 22, 0), "night"))
+        );
+        assert_eq!(
+            changes(&schedule, &time(2024, 6, 3, 21, 0), 3),
+            [
+                (time(2024, 6, 3, 22, 0), "night".to_string()),
+                (time(2024, 6, 4, 6, 0), "day".to_string()),
+                (time(2024, 6, 4, 22, 0), "night".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ended_time_range_keeps_its_layout() {
+        // 2024-06-08 is a Saturday
+        let schedule = schedule(
+            r#"[
+                { "cron": "0 8 * * *", "layout": "base" },
+                { "days": ["sat"], "from": "10:00", "to": "12:00", "layout": "weekend" }
+            ]"#,
+        );
+
+        assert_eq!(
+            changes(&schedule, &time(2024, 6, 8, 9, 0), 2),
+            [
+                (time(2024, 6, 8, 10, 0), "weekend".to_string()),
+                (time(2024, 6, 9, 8, 0), "base".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn sun_entries() {
+        let schedule = schedule(
+            r#"[
+                { "sun": "sunrise", "layout": "day" },
+                { "sun": "sunset", "layout": "night" }
+            ]"#,
+        );
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let sunrise = local_sun_event(&PARIS, date, SunEvent::Sunrise).unwrap();
+        let sunset = local_sun_event(&PARIS, date, SunEvent::Sunset).unwrap();
+        let next_sunrise =
+            local_sun_event(&PARIS, date + Duration::days(1), SunEvent::Sunrise).unwrap();
+        let midday = sunrise + (sunset - sunrise) / 2;
+
+        assert_eq!(schedule.active_at(&midday), Some((sunrise, "day")));
+        assert_eq!(
+            changes(&schedule, &midday, 2),
+            [
+                (sunset, "night".to_string()),
+                (next_sunrise, "day".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn sun_entries_require_a_location() {
+        let json = r#"[{ "sun": "sunset", "layout": "night" }]"#;
+        assert!(Schedule::parse(json, None).is_err());
+    }
+
+    #[test]
+    fn dry_run_reads_the_clock() {
+        let schedule = schedule(
+            r#"[
+                { "cron": "0 9 * * mon-fri", "layout": "work" },
+                { "from": "18:00", "to": "23:00", "layout": "calm" }
+            ]"#,
+        );
+        let clock = FixedClock(time(2024, 6, 7, 20, 0));
+
+        assert_eq!(
+            planned_changes_report(&schedule, &clock, 2),
+            [
+                "Active: calm (since Fri 2024-06-07 18:00)",
+                "Sat 2024-06-08 18:00  calm",
+                "Sun 2024-06-09 18:00  calm",
+            ]
+        );
+    }
+
+    #[test]
+    fn ended_time_range_stays_active() {
+        // 2024-06-03 is a Monday
+        let schedule = schedule(
+            r#"[
+                { "cron": "0 0 * * *", "layout": "base" },
+                { "days": ["mon"], "from": "00:01", "to": "00:02", "layout": "range" }
+            ]"#,
+        );
+        let now = time(2024, 6, 3, 0, 5);
+
+        assert_eq!(
+            schedule.active_at(&now),
+            Some((time(2024, 6, 3, 0, 1), "range"))
+        );
+        assert_eq!(
+            changes(&schedule, &now, 1),
+            [(time(2024, 6, 4, 0, 0), "base".to_string())]
+        );
+    }
+
+    #[test]
+    fn active_layout_is_the_last_planned_change() {
+        let schedule = schedule(
+            r#"[
+                { "cron": "0 8 * * *", "layout": "base" },
+                { "days": ["sat", "sun"], "from": "10:00", "to": "18:00", "layout": "weekend" },
+                { "from": "12:00", "to": "13:00", "layout": "lunch" },
+                { "sun": "sunset", "layout": "evening" }
+            ]"#,
+        );
+
+        let start = time(2024, 6, 6, 0, 0);
+        for change in schedule.planned_changes(&start, 30) {
+            for time in [change.time, change.time + Duration::seconds(30)] {
+                assert_eq!(
+                    schedule.active_at(&time),
+                    Some((change.time, change.layout.as_str())),
+                    "{time}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quarterly_cron_entries_stay_active() {
+        let schedule = schedule(r#"[{ "cron": "0 0 1 jan,apr,jul,oct *", "layout": "quarter" }]"#);
+
+        assert_eq!(
+            schedule.active_at(&time(2024, 6, 20, 12, 0)),
+            Some((time(2024, 4, 1, 0, 0), "quarter"))
+        );
+    }
+}
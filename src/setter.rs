@@ -0,0 +1,654 @@
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::utils::{application_data_path, run_command, shell_quote};
+
+/// Time a --set-command may run for before it is killed.
+const SET_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Way of setting the wallpaper of a desktop environment.
+pub trait WallpaperSetter: std::fmt::Debug {
+    /// Sets the image at `path` as the wallpaper. With `span`, a single image
+    /// stretches across every display instead of being repeated on each.
+    fn set(&self, path: &Path, span: bool) -> Result<()>;
+}
+
+/// Picks the setter of the running desktop from its environment variables, read
+/// with `env`. Falls back to the `wallpaper` crate, which handles MacOS, Windows
+/// and the desktops it knows.
+pub fn detect_setter(env: impl Fn(&str) -> Option<String>) -> Box<dyn WallpaperSetter> {
+    if cfg!(any(target_os = "macos", windows)) {
+        return Box::new(CrateSetter);
+    }
+
+    let path = env("PATH").unwrap_or_default();
+    let find = |program: &str| find_executable(&path, program);
+    let desktops: Vec<String> = env("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .map(|desktop| desktop.to_lowercase())
+        .collect();
+    let on_desktop = |names: &[&str]| {
+        desktops
+            .iter()
+            .any(|desktop| names.contains(&desktop.as_str()))
+    };
+
+    let wayland_compositor = env("SWAYSOCK").is_some()
+        || env("HYPRLAND_INSTANCE_SIGNATURE").is_some()
+        || on_desktop(&["sway", "hyprland", "river", "wayfire"]);
+    if wayland_compositor {
+        if let Some(program) = find("swww") {
+            return Box::new(SwwwSetter { program });
+        }
+        if let Some(program) = find("swaybg") {
+            let pid_file = application_data_path()
+                .ok()
+                .map(|directory| directory.join("swaybg.pid"));
+            return Box::new(SwaybgSetter { program, pid_file });
+        }
+    }
+    if on_desktop(&["kde"]) {
+        if let Some(program) = find("qdbus6").or_else(|| find("qdbus")) {
+            return Box::new(KdeSetter { program });
+        }
+    }
+    if on_desktop(&["xfce"]) {
+        if let Some(program) = find("xfconf-query") {
+            return Box::new(XfconfSetter { program });
+        }
+    }
+    if on_desktop(&["gnome", "unity", "budgie", "pantheon", "ubuntu"]) {
+        if let Some(program) = find("gsettings") {
+            return Box::new(GsettingsSetter { program });
+        }
+    }
+    // Window managers such as i3 leave the wallpaper to feh
+    if env("DISPLAY").is_some() && env("WAYLAND_DISPLAY").is_none() {
+        if let Some(program) = find("feh") {
+            return Box::new(FehSetter { program });
+        }
+    }
+
+    Box::new(CrateSetter)
+}
+
+/// First executable named `program` in the directories of `path`, a PATH value.
+fn find_executable(path: &str, program: &str) -> Option<PathBuf> {
+    std::env::split_paths(path)
+        .map(|directory| directory.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Runs a program to completion, failing with its error output if it fails.
+fn run(program: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| Error::Wallpaper(format!("Failed to run {}. {e}", program.display())))?;
+    if !output.status.success() {
+        return Err(Error::Wallpaper(format!(
+            "{} exited with {}. {}",
+            program.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Error of the setters that cannot stretch one image across every display.
+fn span_unsupported(setter: &str) -> Error {
+    Error::Wallpaper(format!(
+        "{setter} cannot span a panorama across the displays, set it with --set-command instead"
+    ))
+}
+
+fn file_uri(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+/// The `wallpaper` crate, used on MacOS and Windows and when no other setter fits.
+#[derive(Debug)]
+pub struct CrateSetter;
+
+impl WallpaperSetter for CrateSetter {
+    fn set(&self, path: &Path, span: bool) -> Result<()> {
+        if span {
+            wallpaper::set_mode(wallpaper::Mode::Span)
+                .map_err(|e| Error::Wallpaper(e.to_string()))?;
+        }
+        wallpaper::set_from_path(&path.to_string_lossy())
+            .map_err(|e| Error::Wallpaper(e.to_string()))
+    }
+}
+
+/// Shell command given with --set-command, where "{path}" is replaced by the
+/// path of the wallpaper.
+#[derive(Debug)]
+pub struct CommandSetter {
+    pub command: String,
+}
+
+impl WallpaperSetter for CommandSetter {
+    fn set(&self, path: &Path, _span: bool) -> Result<()> {
+        let command = self
+            .command
+            .replace("{path}", &shell_quote(&path.to_string_lossy()));
+        run_command(&command, SET_COMMAND_TIMEOUT).map_err(|e| Error::Wallpaper(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+/// swww, whose daemon crossfades to the new wallpaper on Wayland compositors.
+#[derive(Debug)]
+pub struct SwwwSetter {
+    program: PathBuf,
+}
+
+impl WallpaperSetter for SwwwSetter {
+    fn set(&self, path: &Path, span: bool) -> Result<()> {
+        if span {
+            return Err(span_unsupported("swww"));
+        }
+        run(&self.program, &["img", &path.to_string_lossy()])?;
+        Ok(())
+    }
+}
+
+/// swaybg, which draws the wallpaper for as long as it runs. A new instance is
+/// left running in the background, then the one macground started before is
+/// stopped. Instances started by the user are left alone.
+#[derive(Debug)]
+pub struct SwaybgSetter {
+    program: PathBuf,
+    /// File keeping the process id of the running instance between runs
+    pid_file: Option<PathBuf>,
+}
+
+/// swaybg started by this process, waited for once replaced so it doesn't linger
+/// as a zombie in the daemon.
+static SWAYBG: Mutex<Option<Child>> = Mutex::new(None);
+
+impl WallpaperSetter for SwaybgSetter {
+    fn set(&self, path: &Path, span: bool) -> Result<()> {
+        if span {
+            return Err(span_unsupported("swaybg"));
+        }
+        let child = Command::new(&self.program)
+            .args(["-m", "fill", "-i"])
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| Error::Wallpaper(format!("Failed to start swaybg. {e}")))?;
+        let pid = child.id();
+
+        let previous = SWAYBG
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .replace(child);
+        match previous {
+            Some(mut previous) => {
+                previous.kill().ok();
+                previous.wait().ok();
+            }
+            // Started by a previous run
+            None => {
+                if let Some(previous_pid) = self.read_pid() {
+                    stop_swaybg(previous_pid);
+                }
+            }
+        }
+        if let Some(pid_file) = &self.pid_file {
+            std::fs::write(pid_file, pid.to_string()).ok();
+        }
+
+        Ok(())
+    }
+}
+
+impl SwaybgSetter {
+    fn read_pid(&self) -> Option<u32> {
+        let pid_file = self.pid_file.as_ref()?;
+        std::fs::read_to_string(pid_file).ok()?.trim().parse().ok()
+    }
+}
+
+/// Stops the process `pid` if it is still a swaybg, its id may have been reused since.
+fn stop_swaybg(pid: u32) {
+    let name = std::fs::read_to_string(format!("/proc/{pid}/comm")).unwrap_or_default();
+    if name.trim() == "swaybg" {
+        Command::new("kill").arg(pid.to_string()).status().ok();
+    }
+}
+
+/// feh, for X11 window managers such as i3.
+#[derive(Debug)]
+pub struct FehSetter {
+    program: PathBuf,
+}
+
+impl WallpaperSetter for FehSetter {
+    fn set(&self, path: &Path, span: bool) -> Result<()> {
+        let path = path.to_string_lossy();
+        let args: &[&str] = if span {
+            &["--no-xinerama", "--bg-fill", &path]
+        } else {
+            &["--bg-fill", &path]
+        };
+        run(&self.program, args)?;
+        Ok(())
+    }
+}
+
+/// gsettings, for GNOME and the desktops built on it.
+#[derive(Debug)]
+pub struct GsettingsSetter {
+    program: PathBuf,
+}
+
+impl WallpaperSetter for GsettingsSetter {
+    fn set(&self, path: &Path, span: bool) -> Result<()> {
+        let schema = "org.gnome.desktop.background";
+        let uri = file_uri(path);
+        let options = if span { "spanned" } else { "zoom" };
+        run(&self.program, &["set", schema, "picture-options", options])?;
+        run(&self.program, &["set", schema, "picture-uri", &uri])?;
+        // Only GNOME 42 and later have a separate wallpaper for the dark style
+        run(&self.program, &["set", schema, "picture-uri-dark", &uri]).ok();
+
+        Ok(())
+    }
+}
+
+/// xfconf-query, for XFCE, which keeps one wallpaper per monitor and workspace.
+/// A panorama is set on every monitor with the spanning screens style.
+#[derive(Debug)]
+pub struct XfconfSetter {
+    program: PathBuf,
+}
+
+impl WallpaperSetter for XfconfSetter {
+    fn set(&self, path: &Path, span: bool) -> Result<()> {
+        let properties = run(&self.program, &["-c", "xfce4-desktop", "-l"])?;
+        let images: Vec<&str> = properties
+            .lines()
+            .map(str::trim)
+            .filter(|property| property.ends_with("/last-image"))
+            .collect();
+        if images.is_empty() {
+            return Err(Error::Wallpaper(
+                "XFCE has no desktop wallpaper properties".to_string(),
+            ));
+        }
+
+        let path = path.to_string_lossy();
+        // Image styles 5 and 6 are zoomed and spanning screens
+        let style = if span { "6" } else { "5" };
+        for property in images {
+            run(
+                &self.program,
+                &["-c", "xfce4-desktop", "-p", property, "-s", &path],
+            )?;
+            let style_property = property.replace("/last-image", "/image-style");
+            run(
+                &self.program,
+                &[
+                    "-c",
+                    "xfce4-desktop",
+                    "-p",
+                    &style_property,
+                    "-n",
+                    "-t",
+                    "int",
+                    "-s",
+                    style,
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Plasma's scripting interface, called over D-Bus with qdbus.
+#[derive(Debug)]
+pub struct KdeSetter {
+    program: PathBuf,
+}
+
+impl WallpaperSetter for KdeSetter {
+    fn set(&self, path: &Path, span: bool) -> Result<()> {
+        if span {
+            return Err(span_unsupported("Plasma"));
+        }
+        let uri = file_uri(path).replace('\\', "\\\\").replace('"', "\\\"");
+        let script = format!(
+            r#"desktops().forEach(desktop => {{
+                desktop.wallpaperPlugin = "org.kde.image";
+                desktop.currentConfigGroup = ["Wallpaper", "org.kde.image", "General"];
+                desktop.writeConfig("Image", "{uri}");
+            }});"#
+        );
+        run(
+            &self.program,
+            &[
+                "org.kde.plasmashell",
+                "/PlasmaShell",
+                "org.kde.PlasmaShell.evaluateScript",
+                &script,
+            ],
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Setter of the output options: the --set-command if given, otherwise the
+/// detected one.
+pub fn wallpaper_setter(set_command: Option<&str>) -> Box<dyn WallpaperSetter> {
+    match set_command {
+        Some(command) => Box::new(CommandSetter {
+            command: command.to_string(),
+        }),
+        None => detect_setter(|name| std::env::var(name).ok()),
+    }
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use std::collections::HashMap;
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    /// Directory of stub executables logging their arguments to its "log" file.
+    struct Stubs {
+        directory: PathBuf,
+    }
+
+    impl Stubs {
+        fn new(test: &str, programs: &[&str]) -> Self {
+            let directory =
+                std::env::temp_dir().join(format!("macground-{test}-{}", std::process::id()));
+            std::fs::remove_dir_all(&directory).ok();
+            std::fs::create_dir_all(&directory).unwrap();
+            let stubs = Self { directory };
+            for program in programs {
+                stubs.add(program, "");
+            }
+            stubs
+        }
+
+        /// Adds a stub running `body` after logging its arguments.
+        fn add(&self, program: &str, body: &str) {
+            let log = self.directory.join("log");
+            let path = self.directory.join(program);
+            let script = format!(
+                "#!/bin/sh\nfor arg in \"$@\"; do printf '%s|' \"$arg\" >> '{}'; done\necho >> '{}'\n{body}\n",
+                log.display(),
+                log.display()
+            );
+            std::fs::write(&path, script).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        fn path(&self, program: &str) -> PathBuf {
+            self.directory.join(program)
+        }
+
+        /// Arguments of every call, one line per call with "|" after each argument.
+        fn calls(&self) -> Vec<String> {
+            std::fs::read_to_string(self.directory.join("log"))
+                .unwrap_or_default()
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+
+        fn detect(&self, vars: &[(&str, &str)]) -> String {
+            let mut env: HashMap<String, String> = vars
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            env.insert("PATH".to_string(), self.directory.display().to_string());
+            let setter = detect_setter(|name| env.get(name).cloned());
+            let name = format!("{setter:?}");
+            name.split([' ', '{'])
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        }
+    }
+
+    impl Drop for Stubs {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.directory).ok();
+        }
+    }
+
+    const ALL: [&str; 7] = [
+        "swww",
+        "swaybg",
+        "qdbus",
+        "xfconf-query",
+        "gsettings",
+        "feh",
+        "pkill",
+    ];
+
+    #[test]
+    fn detects_the_setter_of_the_desktop() {
+        let stubs = Stubs::new("detect", &ALL);
+        let cases: [(&[(&str, &str)], &str); 12] = [
+            (&[("SWAYSOCK", "/run/sway.sock")], "SwwwSetter"),
+            (&[("HYPRLAND_INSTANCE_SIGNATURE", "abc")], "SwwwSetter"),
+            (&[("XDG_CURRENT_DESKTOP", "river")], "SwwwSetter"),
+            (&[("XDG_CURRENT_DESKTOP", "KDE")], "KdeSetter"),
+            (&[("XDG_CURRENT_DESKTOP", "XFCE")], "XfconfSetter"),
+            (&[("XDG_CURRENT_DESKTOP", "GNOME")], "GsettingsSetter"),
+            (
+                &[("XDG_CURRENT_DESKTOP", "ubuntu:GNOME")],
+                "GsettingsSetter",
+            ),
+            (&[("XDG_CURRENT_DESKTOP", "Budgie")], "GsettingsSetter"),
+            (&[("DISPLAY", ":0")], "FehSetter"),
+            (
+                &[("DISPLAY", ":0"), ("WAYLAND_DISPLAY", "wayland-0")],
+                "CrateSetter",
+            ),
+            (&[("XDG_CURRENT_DESKTOP", "unknown")], "CrateSetter"),
+            (&[], "CrateSetter"),
+        ];
+
+        for (vars, expected) in cases {
+            assert_eq!(stubs.detect(vars), expected, "{vars:?}");
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_programs_available() {
+        let stubs = Stubs::new("fallback", &["swaybg", "qdbus6", "feh"]);
+        assert_eq!(
+            stubs.detect(&[("SWAYSOCK", "/run/sway.sock")]),
+            "SwaybgSetter"
+        );
+        assert_eq!(stubs.detect(&[("XDG_CURRENT_DESKTOP", "KDE")]), "KdeSetter");
+        assert_eq!(
+            stubs.detect(&[("XDG_CURRENT_DESKTOP", "GNOME"), ("DISPLAY", ":0")]),
+            "FehSetter"
+        );
+        assert_eq!(
+            stubs.detect(&[("XDG_CURRENT_DESKTOP", "XFCE")]),
+            "CrateSetter"
+        );
+    }
+
+    #[test]
+    fn swww_arguments() {
+        let stubs = Stubs::new("swww", &["swww"]);
+        let setter = SwwwSetter {
+            program: stubs.path("swww"),
+        };
+        setter.set(Path::new("/tmp/a b.png"), false).unwrap();
+        assert!(setter.set(Path::new("/tmp/a b.png"), true).is_err());
+        assert_eq!(stubs.calls(), ["img|/tmp/a b.png|"]);
+    }
+
+    #[test]
+    fn feh_arguments() {
+        let stubs = Stubs::new("feh", &["feh"]);
+        let setter = FehSetter {
+            program: stubs.path("feh"),
+        };
+        setter.set(Path::new("/tmp/a.png"), false).unwrap();
+        setter.set(Path::new("/tmp/a.png"), true).unwrap();
+        assert_eq!(
+            stubs.calls(),
+            [
+                "--bg-fill|/tmp/a.png|",
+                "--no-xinerama|--bg-fill|/tmp/a.png|"
+            ]
+        );
+    }
+
+    #[test]
+    fn gsettings_arguments() {
+        let stubs = Stubs::new("gsettings", &["gsettings"]);
+        let setter = GsettingsSetter {
+            program: stubs.path("gsettings"),
+        };
+        setter.set(Path::new("/tmp/a.png"), true).unwrap();
+        assert_eq!(
+            stubs.calls(),
+            [
+                "set|org.gnome.desktop.background|picture-options|spanned|",
+                "set|org.gnome.desktop.background|picture-uri|file:///tmp/a.png|",
+                "set|org.gnome.desktop.background|picture-uri-dark|file:///tmp/a.png|",
+            ]
+        );
+    }
+
+    #[test]
+    fn xfconf_arguments() {
+        let stubs = Stubs::new("xfconf", &[]);
+        stubs.add(
+            "xfconf-query",
+            r#"if [ "$3" = "-l" ]; then printf '%s\n' /backdrop/screen0/monitorDP-1/workspace0/last-image /backdrop/screen0/monitorDP-1/workspace0/color-style; fi"#,
+        );
+        let setter = XfconfSetter {
+            program: stubs.path("xfconf-query"),
+        };
+        setter.set(Path::new("/tmp/a.png"), true).unwrap();
+        assert_eq!(
+            stubs.calls(),
+            [
+                "-c|xfce4-desktop|-l|",
+                "-c|xfce4-desktop|-p|/backdrop/screen0/monitorDP-1/workspace0/last-image|-s|/tmp/a.png|",
+                "-c|xfce4-desktop|-p|/backdrop/screen0/monitorDP-1/workspace0/image-style|-n|-t|int|-s|6|",
+            ]
+        );
+    }
+
+    #[test]
+    fn xfconf_without_wallpapers_fails() {
+        let stubs = Stubs::new("xfconf-empty", &["xfconf-query"]);
+        let setter = XfconfSetter {
+            program: stubs.path("xfconf-query"),
+        };
+        assert!(setter.set(Path::new("/tmp/a.png"), false).is_err());
+    }
+
+    #[test]
+    fn kde_arguments() {
+        let stubs = Stubs::new("kde", &["qdbus"]);
+        let setter = KdeSetter {
+            program: stubs.path("qdbus"),
+        };
+        setter.set(Path::new("/tmp/a\"b.png"), false).unwrap();
+        assert!(setter.set(Path::new("/tmp/a.png"), true).is_err());
+
+        // The script spans several lines of the log
+        let log = stubs.calls().join("\n");
+        assert!(
+            log.starts_with("org.kde.plasmashell|/PlasmaShell|org.kde.PlasmaShell.evaluateScript|")
+        );
+        assert!(log.contains(r#"desktop.writeConfig("Image", "file:///tmp/a\"b.png");"#));
+        assert_eq!(log.matches("evaluateScript").count(), 1);
+    }
+
+    #[test]
+    fn failing_program_is_an_error() {
+        let stubs = Stubs::new("failing", &[]);
+        stubs.add("swww", "echo 'daemon not running' >&2; exit 1");
+        let setter = SwwwSetter {
+            program: stubs.path("swww"),
+        };
+        let error = setter.set(Path::new("/tmp/a.png"), false).unwrap_err();
+        assert!(error.to_string().contains("daemon not running"), "{error}");
+    }
+
+    #[test]
+    fn swaybg_replaces_its_own_instance() {
+        let stubs = Stubs::new("swaybg", &[]);
+        stubs.add("swaybg", "exec sleep 30");
+        let pid_file = stubs.directory.join("swaybg.pid");
+        let setter = SwaybgSetter {
+            program: stubs.path("swaybg"),
+            pid_file: Some(pid_file.clone()),
+        };
+        let read_pid = || std::fs::read_to_string(&pid_file).unwrap();
+
+        // Give each stub time to log before it gets replaced
+        setter.set(Path::new("/tmp/a.png"), false).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let first = read_pid();
+        setter.set(Path::new("/tmp/b.png"), false).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let second = read_pid();
+        assert!(setter.set(Path::new("/tmp/b.png"), true).is_err());
+
+        // The first instance was killed and reaped, the second keeps running
+        assert!(!Path::new(&format!("/proc/{first}")).exists());
+        assert!(Path::new(&format!("/proc/{second}")).exists());
+        if let Some(mut child) = SWAYBG.lock().unwrap().take() {
+            child.kill().ok();
+            child.wait().ok();
+        }
+
+        assert_eq!(
+            stubs.calls(),
+            ["-m|fill|-i|/tmp/a.png|", "-m|fill|-i|/tmp/b.png|"]
+        );
+    }
+
+    #[test]
+    fn set_command_quotes_the_path() {
+        let stubs = Stubs::new("command", &["setter"]);
+        let setter = CommandSetter {
+            command: format!("{} --image {{path}}", stubs.path("setter").display()),
+        };
+        setter.set(Path::new("/tmp/it's a.png"), false).unwrap();
+        assert_eq!(stubs.calls(), ["--image|/tmp/it's a.png|"]);
+    }
+}
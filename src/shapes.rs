@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+use tiny_skia::{
+    FillRule, LineCap, LineJoin, Paint, PathBuilder, Pixmap, PremultipliedColorU8, StrokeDash,
+    Transform,
+};
+
+use crate::display::Region;
+use crate::error::{Error, Result};
+use crate::palette::SymbolicColors;
+
+/// Point given as `[x, y]`, in points from the top left corner of the primary display.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Point(pub f32, pub f32);
+
+/// Cubic Bézier curve from the end of the previous segment of a path.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct CubicSegment {
+    pub control1: Point,
+    pub control2: Point,
+    pub to: Point,
+}
+
+/// Outline of a shape. Coordinates and lengths are in points, which are pixels
+/// on displays with a scale factor of 1.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Geometry {
+    Line {
+        from: Point,
+        to: Point,
+    },
+    Polyline {
+        points: Vec<Point>,
+    },
+    /// Rectangle with corners rounded to `radius`
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        #[serde(default)]
+        radius: f32,
+    },
+    Circle {
+        center: Point,
+        radius: f32,
+    },
+    Ellipse {
+        center: Point,
+        radius_x: f32,
+        radius_y: f32,
+    },
+    /// Closed outline through the points
+    Polygon {
+        points: Vec<Point>,
+    },
+    /// Sequence of cubic Bézier curves starting at `start`
+    Path {
+        start: Point,
+        curves: Vec<CubicSegment>,
+        #[serde(default)]
+        closed: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Cap {
+    #[default]
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Join {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Outline drawn along a shape.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Stroke {
+    /// Any CSS color or symbolic color
+    pub color: String,
+    #[serde(default = "default_stroke_width")]
+    pub width: f32,
+    #[serde(default)]
+    pub cap: Cap,
+    #[serde(default)]
+    pub join: Join,
+    /// Alternating lengths of dashes and gaps, a solid line if empty
+    #[serde(default)]
+    pub dash: Vec<f32>,
+}
+
+fn default_stroke_width() -> f32 {
+    1.0
+}
+
+/// Shape drawn over the background, filled and/or stroked.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Shape {
+    #[serde(flatten)]
+    pub geometry: Geometry,
+    /// Any CSS color or symbolic color, the shape is not filled if [None]
+    #[serde(default)]
+    pub fill: Option<String>,
+    #[serde(default)]
+    pub stroke: Option<Stroke>,
+}
+
+impl Shape {
+    /// Parses a shape from its JSON description.
+    pub fn parse(raw_shape: &str) -> std::result::Result<Self, String> {
+        serde_json::from_str(raw_shape).map_err(|e| format!("Invalid shape {raw_shape}. {e}"))
+    }
+}
+
+fn build_path(geometry: &Geometry) -> Option<tiny_skia::Path> {
+    let mut builder = PathBuilder::new();
+    let mut points_path = |points: &[Point], close: bool| {
+        let (first, rest) = points.split_first()?;
+        builder.move_to(first.0, first.1);
+        for point in rest {
+            builder.line_to(point.0, point.1);
+        }
+        if close {
+            builder.close();
+        }
+        Some(())
+    };
+
+    match geometry {
+        Geometry::Line { from, to } => points_path(&[*from, *to], false)?,
+        Geometry::Polyline { points } => points_path(points, false)?,
+        Geometry::Polygon { points } => points_path(points, true)?,
+        Geometry::Rect {
+            x,
+            y,
+            width,
+            height,
+            radius,
+        } => return rounded_rect(*x, *y, *width, *height, *radius),
+        Geometry::Circle { center, radius } => {
+            return PathBuilder::from_circle(center.0, center.1, *radius)
+        }
+        Geometry::Ellipse {
+            center,
+            radius_x,
+            radius_y,
+        } => {
+            let bounds = tiny_skia::Rect::from_xywh(
+                center.0 - radius_x,
+                center.1 - radius_y,
+                2.0 * radius_x,
+                2.0 * radius_y,
+            )?;
+            return PathBuilder::from_oval(bounds);
+        }
+        Geometry::Path {
+            start,
+            curves,
+            closed,
+        } => {
+            builder.move_to(start.0, start.1);
+            for curve in curves {
+                builder.cubic_to(
+                    curve.control1.0,
+                    curve.control1.1,
+                    curve.control2.0,
+                    curve.control2.1,
+                    curve.to.0,
+                    curve.to.1,
+                );
+            }
+            if *closed {
+                builder.close();
+            }
+        }
+    }
+
+    builder.finish()
+}
+
+fn rounded_rect(x: f32, y: f32, width: f32, height: f32, radius: f32) -> Option<tiny_skia::Path> {
+    let radius = radius.clamp(0.0, width.min(height) / 2.0);
+    if radius == 0.0 {
+        return Some(PathBuilder::from_rect(tiny_skia::Rect::from_xywh(
+            x, y, width, height,
+        )?));
+    }
+
+    // Quarter circles approximated by cubic Béziers
+    let handle = radius * (1.0 - 0.552_284_8);
+    let (right, bottom) = (x + width, y + height);
+    let mut builder = PathBuilder::new();
+    builder.move_to(x + radius, y);
+    builder.line_to(right - radius, y);
+    builder.cubic_to(right - handle, y, right, y + handle, right, y + radius);
+    builder.line_to(right, bottom - radius);
+    builder.cubic_to(
+        right,
+        bottom - handle,
+        right - handle,
+        bottom,
+        right - radius,
+        bottom,
+    );
+    builder.line_to(x + radius, bottom);
+    builder.cubic_to(x + handle, bottom, x, bottom - handle, x, bottom - radius);
+    builder.line_to(x, y + radius);
+    builder.cubic_to(x, y + handle, x + handle, y, x + radius, y);
+    builder.close();
+
+    builder.finish()
+}
+
+fn paint(raw_color: &str, colors: &SymbolicColors) -> Result<Paint<'static>> {
+    let [red, green, blue, alpha] = colors.parse(raw_color)?;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(red, green, blue, alpha);
+    paint.anti_alias = true;
+
+    Ok(paint)
+}
+
+fn stroke_style(stroke: &Stroke) -> Result<tiny_skia::Stroke> {
+    // Like SVG, an odd number of dash lengths is repeated to make it even
+    let mut dash = stroke.dash.clone();
+    if dash.len() % 2 == 1 {
+        dash.extend_from_within(..);
+    }
+    let dash = if dash.is_empty() {
+        None
+    } else {
+        Some(
+            StrokeDash::new(dash, 0.0)
+                .ok_or_else(|| Error::Config(format!("Invalid stroke dash {:?}", stroke.dash)))?,
+        )
+    };
+
+    Ok(tiny_skia::Stroke {
+        width: stroke.width,
+        line_cap: match stroke.cap {
+            Cap::Butt => LineCap::Butt,
+            Cap::Round => LineCap::Round,
+            Cap::Square => LineCap::Square,
+        },
+        line_join: match stroke.join {
+            Join::Miter => LineJoin::Miter,
+            Join::Round => LineJoin::Round,
+            Join::Bevel => LineJoin::Bevel,
+        },
+        dash,
+        ..Default::default()
+    })
+}
+
+/// Renders shapes with antialiasing onto a transparent image the size of
+/// `region`, scaling their coordinates by `scale_factor`. Their symbolic colors
+/// are resolved with `colors`.
+pub fn render_shapes(
+    shapes: &[Shape],
+    region: &Region,
+    scale_factor: f32,
+    colors: &SymbolicColors,
+) -> Result<Pixmap> {
+    let mut pixmap = Pixmap::new(region.width.max(1), region.height.max(1))
+        .ok_or_else(|| Error::Config("Invalid region to draw shapes in".to_string()))?;
+    let transform = Transform::from_scale(scale_factor, scale_factor);
+
+    for shape in shapes {
+        let Some(path) = build_path(&shape.geometry) else {
+            continue;
+        };
+        if let Some(fill) = &shape.fill {
+            pixmap.fill_path(
+                &path,
+                &paint(fill, colors)?,
+                FillRule::Winding,
+                transform,
+                None,
+            );
+        }
+        if let Some(stroke) = &shape.stroke {
+            pixmap.stroke_path(
+                &path,
+                &paint(&stroke.color, colors)?,
+                &stroke_style(stroke)?,
+                transform,
+                None,
+            );
+        }
+    }
+
+    Ok(pixmap)
+}
+
+/// Renders a filled rectangle with rounded corners, with antialiasing, onto a
+/// transparent image the size of `region`.
+pub fn render_rounded_rect(region: &Region, radius: f32, color: [u8; 4]) -> Result<Pixmap> {
+    let mut pixmap = Pixmap::new(region.width.max(1), region.height.max(1))
+        .ok_or_else(|| Error::Config("Invalid region to draw shapes in".to_string()))?;
+    let [red, green, blue, alpha] = color;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(red, green, blue, alpha);
+    paint.anti_alias = true;
+
+    if let Some(path) = rounded_rect(0.0, 0.0, region.width as f32, region.height as f32, radius) {
+        pixmap.fill_path(
+            &path,
+            &paint,
+            FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+    }
+
+    Ok(pixmap)
+}
+
+/// Straight RGBA color of a premultiplied pixel.
+pub fn unpremultiply(pixel: PremultipliedColorU8) -> [u8; 4] {
+    let color = pixel.demultiply();
+    [color.red(), color.green(), color.blue(), color.alpha()]
+}
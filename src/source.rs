@@ -1,17 +1,34 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::Duration;
 
-use image::Rgba;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use chrono_tz::Tz;
+use image::{ImageBuffer, Rgba};
 use rand::distributions::Uniform;
-use rand::{thread_rng, Rng};
-use reqwest;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
 use serde::Deserialize;
 use word_generator::{langs, *};
 
+use crate::args::{QuoteFileFormat, QuoteOptions, QuoteOrder, TodoOptions, WordCase, WordOptions};
+use crate::dictionary::{entry_of_the_day, read_dictionary};
+use crate::error::{Error, Result};
+use crate::preferences::Preferences;
+use crate::quote_file::select_quote;
+use crate::quotes::{cache_quote, local_quotes, random_quote, Quote};
+use crate::sun::SunPosition;
+use crate::svg::Svg;
+use crate::todo::{filter_items, read_todo_file, TodoItem};
+use crate::utils::run_command;
 use crate::BackgroundImage;
 
 pub trait Source {
-    fn get_background(&self) -> BackgroundImage;
+    fn get_background(&self) -> Result<BackgroundImage>;
 }
 
 pub struct ColorSource {
@@ -28,19 +45,15 @@ impl ColorSource {
             color,
         }
     }
-    pub fn random(width: u32, height: u32) -> Self {
-        ColorSource::new(width, height, random_color())
-    }
 }
 
 impl Source for ColorSource {
-    fn get_background(&self) -> BackgroundImage {
-        BackgroundImage::new(self.width, self.height, &self.color)
+    fn get_background(&self) -> Result<BackgroundImage> {
+        Ok(BackgroundImage::new(self.width, self.height, &self.color))
     }
 }
 
-pub fn random_color() -> Rgba<u8> {
-    let mut rng = thread_rng();
+pub fn random_color(rng: &mut impl Rng) -> Rgba<u8> {
     let r = rng.sample(Uniform::new(0, 255));
     let g = rng.sample(Uniform::new(0, 255));
     let b = rng.sample(Uniform::new(0, 255));
@@ -48,6 +61,135 @@ pub fn random_color() -> Rgba<u8> {
     [r, g, b, 255].into()
 }
 
+/// Colors of the sky (top and bottom of the background) at a solar elevation.
+struct SkyKeyframe {
+    elevation: f64,
+    top: [u8; 3],
+    bottom: [u8; 3],
+}
+
+const fn keyframe(elevation: f64, top: u32, bottom: u32) -> SkyKeyframe {
+    const fn rgb(color: u32) -> [u8; 3] {
+        [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+    }
+    SkyKeyframe {
+        elevation,
+        top: rgb(top),
+        bottom: rgb(bottom),
+    }
+}
+
+/// Sky colors from night, through twilight and sunrise, to noon.
+const DAWN_SKY: [SkyKeyframe; 6] = [
+    keyframe(-18.0, 0x0b1026, 0x1c2541),
+    keyframe(-6.0, 0x2b2f77, 0x8e6fb1),
+    keyframe(0.0, 0x4a5fa8, 0xf7b2ad),
+    keyframe(6.0, 0x5a8fd0, 0xfddcb5),
+    keyframe(20.0, 0x2f7fe0, 0xa7d4f7),
+    keyframe(60.0, 0x0e5fd8, 0xc4e6ff),
+];
+
+/// Sky colors from noon, through sunset and twilight, to night.
+const DUSK_SKY: [SkyKeyframe; 6] = [
+    keyframe(-18.0, 0x0b1026, 0x1c2541),
+    keyframe(-6.0, 0x23265e, 0x6d3b6e),
+    keyframe(0.0, 0x3d4e8f, 0xff7b54),
+    keyframe(6.0, 0x5280c2, 0xffb26b),
+    keyframe(20.0, 0x2f7fe0, 0xa7d4f7),
+    keyframe(60.0, 0x0e5fd8, 0xc4e6ff),
+];
+
+/// Vertical gradient colored like the sky at a position of the sun.
+pub struct SkySource {
+    width: u32,
+    height: u32,
+    sun: SunPosition,
+}
+
+impl SkySource {
+    pub fn new(width: u32, height: u32, sun: SunPosition) -> Self {
+        Self { width, height, sun }
+    }
+
+    /// Top and bottom colors of the sky, interpolated between the keyframes
+    /// surrounding the sun's elevation.
+    fn colors(&self) -> ([u8; 3], [u8; 3]) {
+        let keyframes = if self.sun.rising {
+            &DAWN_SKY
+        } else {
+            &DUSK_SKY
+        };
+        let elevation = self.sun.elevation.clamp(
+            keyframes[0].elevation,
+            keyframes[keyframes.len() - 1].elevation,
+        );
+
+        let upper = keyframes
+            .iter()
+            .position(|keyframe| keyframe.elevation >= elevation)
+            .unwrap_or(keyframes.len() - 1)
+            .max(1);
+        let (from, to) = (&keyframes[upper - 1], &keyframes[upper]);
+        let t = (elevation - from.elevation) / (to.elevation - from.elevation);
+
+        (
+            mix_rgb(from.top, to.top, t),
+            mix_rgb(from.bottom, to.bottom, t),
+        )
+    }
+}
+
+fn mix_rgb(from: [u8; 3], to: [u8; 3], t: f64) -> [u8; 3] {
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    [
+        mix(from[0], to[0]),
+        mix(from[1], to[1]),
+        mix(from[2], to[2]),
+    ]
+}
+
+impl Source for SkySource {
+    fn get_background(&self) -> Result<BackgroundImage> {
+        let (top, bottom) = self.colors();
+        GradientSource::new(self.width, self.height, top, bottom).get_background()
+    }
+}
+
+/// Vertical gradient between two colors.
+pub struct GradientSource {
+    width: u32,
+    height: u32,
+    top: [u8; 3],
+    bottom: [u8; 3],
+}
+
+impl GradientSource {
+    pub fn new(width: u32, height: u32, top: [u8; 3], bottom: [u8; 3]) -> Self {
+        Self {
+            width,
+            height,
+            top,
+            bottom,
+        }
+    }
+}
+
+impl Source for GradientSource {
+    fn get_background(&self) -> Result<BackgroundImage> {
+        let denominator = self.height.saturating_sub(1).max(1) as f64;
+        let rows: Vec<[u8; 3]> = (0..self.height)
+            .map(|y| mix_rgb(self.top, self.bottom, y as f64 / denominator))
+            .collect();
+
+        let buffer = ImageBuffer::from_fn(self.width, self.height, |_, y| {
+            let [r, g, b] = rows[y as usize];
+            Rgba([r, g, b, 255])
+        });
+
+        Ok(BackgroundImage::from(buffer))
+    }
+}
+
 #[derive(Default)]
 pub struct ImageSource {
     image_url: String,
@@ -66,9 +208,9 @@ impl ImageSource {
 }
 
 impl Source for ImageSource {
-    fn get_background(&self) -> BackgroundImage {
-        let response = reqwest::blocking::get(&self.image_url).unwrap();
-        let image = image::load_from_memory(&response.bytes().unwrap()).unwrap();
+    fn get_background(&self) -> Result<BackgroundImage> {
+        let response = reqwest::blocking::get(&self.image_url)?.error_for_status()?;
+        let image = image::load_from_memory(&response.bytes()?)?;
         let image = image.resize_to_fill(
             self.width,
             self.height,
@@ -76,23 +218,256 @@ impl Source for ImageSource {
         );
         let buffer = image.into_rgba8();
 
-        BackgroundImage::from(buffer)
+        Ok(BackgroundImage::from(buffer))
+    }
+}
+
+/// [Source] for an SVG file rasterized at the resolution of the background,
+/// either covering it or repeated as tiles. Transparent parts are black.
+pub struct SvgSource {
+    width: u32,
+    height: u32,
+    path: PathBuf,
+    /// Width of the tiles in pixels, the SVG covers the background if [None]
+    tile_width: Option<u32>,
+}
+
+impl SvgSource {
+    pub fn new(width: u32, height: u32, path: PathBuf, tile_width: Option<u32>) -> Self {
+        Self {
+            width,
+            height,
+            path,
+            tile_width,
+        }
+    }
+}
+
+impl Source for SvgSource {
+    fn get_background(&self) -> Result<BackgroundImage> {
+        let svg = Svg::open(&self.path)?;
+        let mut background = BackgroundImage::new(self.width, self.height, &Rgba([0, 0, 0, 255]));
+
+        match self.tile_width {
+            Some(tile_width) => {
+                let tile = svg.render_width(tile_width.max(1))?;
+                for y in (0..self.height).step_by(tile.height().max(1) as usize) {
+                    for x in (0..self.width).step_by(tile.width() as usize) {
+                        background.draw_image(&tile, x, y, 1.0);
+                    }
+                }
+            }
+            None => {
+                // Scaled to cover the background, then cropped to its center
+                let (svg_width, svg_height) = svg.size();
+                let scale = (self.width as f32 / svg_width).max(self.height as f32 / svg_height);
+                let width = ((svg_width * scale).ceil() as u32).max(self.width);
+                let height = ((svg_height * scale).ceil() as u32).max(self.height);
+                let rendered = svg.render(width, height)?;
+                let cropped = image::imageops::crop_imm(
+                    &rendered,
+                    (width - self.width) / 2,
+                    (height - self.height) / 2,
+                    self.width,
+                    self.height,
+                )
+                .to_image();
+                background.draw_image(&cropped, 0, 0, 1.0);
+            }
+        }
+
+        Ok(background)
     }
 }
 
 pub trait TextSource {
-    fn source_text(&self) -> Vec<String>;
+    fn source_text(&self) -> Result<Vec<String>>;
 }
 
-/// [TextSource] for generating a random word.
-#[derive(Default)]
-pub struct RandomWordSource;
+/// [TextSource] for the contents of a file.
+pub struct MessageFileSource {
+    path: PathBuf,
+}
+
+impl MessageFileSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl TextSource for MessageFileSource {
+    fn source_text(&self) -> Result<Vec<String>> {
+        let contents = std::fs::read_to_string(&self.path).map_err(|e| {
+            Error::Config(format!(
+                "Failed to read message file {}. {e}",
+                self.path.display()
+            ))
+        })?;
+        Ok(non_empty_message(contents.trim()))
+    }
+}
+
+/// [TextSource] for the output of a shell command.
+pub struct CommandSource {
+    command: String,
+    timeout: Duration,
+}
+
+impl CommandSource {
+    pub fn new(command: String, timeout: Duration) -> Self {
+        Self { command, timeout }
+    }
+}
+
+impl TextSource for CommandSource {
+    fn source_text(&self) -> Result<Vec<String>> {
+        let output = run_command(&self.command, self.timeout)?;
+        Ok(non_empty_message(&output))
+    }
+}
+
+/// An empty message displays no text.
+fn non_empty_message(message: &str) -> Vec<String> {
+    if message.is_empty() {
+        vec![]
+    } else {
+        vec![message.to_string()]
+    }
+}
+
+/// Attempts at generating words with the requested number of syllables.
+const WORD_ATTEMPTS: u32 = 100;
+/// Words generated per attempt.
+const WORDS_PER_ATTEMPT: u32 = 50;
+
+/// [TextSource] for generating random words.
+///
+/// Real words are picked with `rng`, pseudo-words are generated with an
+/// unseeded RNG of the word generator.
+pub struct RandomWordSource<'a> {
+    options: WordOptions,
+    rng: RefCell<&'a mut StdRng>,
+}
+
+impl<'a> RandomWordSource<'a> {
+    pub fn new(options: WordOptions, rng: &'a mut StdRng) -> Self {
+        Self {
+            options,
+            rng: RefCell::new(rng),
+        }
+    }
+
+    /// List of words of the language, one per line.
+    fn word_list(&self) -> Result<Vec<u8>> {
+        match self.options.language.as_str() {
+            "fr" => Ok(langs::FR_TXT.to_vec()),
+            path => std::fs::read(path)
+                .map_err(|e| Error::Config(format!("Failed to read the word list {path}. {e}"))),
+        }
+    }
+
+    fn has_syllables(&self, word: &str) -> bool {
+        self.options
+            .syllables
+            .is_none_or(|syllables| count_syllables(word) == syllables)
+    }
+
+    /// Real words picked from the word list.
+    fn pick_words(&self, word_list: &[u8]) -> Vec<String> {
+        let words: Vec<&str> = std::str::from_utf8(word_list)
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|word| !word.is_empty() && !word.contains(' '))
+            .filter(|word| self.has_syllables(word))
+            .collect();
+
+        words
+            .choose_multiple(*self.rng.borrow_mut(), self.options.count)
+            .map(|word| word.to_string())
+            .collect()
+    }
+
+    /// Pseudo-words generated from the word list.
+    fn generate_words(&self, word_list: &[u8]) -> Result<Vec<String>> {
+        let table = ProbabilityTable::from_reader(BufReader::new(word_list), 3)?;
+
+        let mut words = vec![];
+        for _ in 0..WORD_ATTEMPTS {
+            words.extend(
+                table
+                    .generate_words(WORDS_PER_ATTEMPT)
+                    .into_iter()
+                    .filter(|word| self.has_syllables(word)),
+            );
+            if words.len() >= self.options.count {
+                break;
+            }
+        }
+        words.truncate(self.options.count);
+
+        Ok(words)
+    }
+}
+
+impl TextSource for RandomWordSource<'_> {
+    /// Returns the words separated by spaces
+    fn source_text(&self) -> Result<Vec<String>> {
+        let word_list = self.word_list()?;
+        let words = if self.options.real_words {
+            self.pick_words(&word_list)
+        } else {
+            self.generate_words(&word_list)?
+        };
+        if words.len() < self.options.count {
+            return Err(Error::Config(format!(
+                "Not enough words matching the word options in \"{}\"",
+                self.options.language
+            )));
+        }
 
-impl TextSource for RandomWordSource {
-    fn source_text(&self) -> Vec<String> {
-        let reader = BufReader::new(langs::FR_TXT);
-        let word = generate_words(reader, 3, 1).unwrap()[0].to_owned();
-        vec![word]
+        let words: Vec<String> = words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| change_case(word, self.options.case, i == 0))
+            .collect();
+        Ok(vec![words.join(" ")])
+    }
+}
+
+/// Estimates the number of syllables of a word from its groups of vowels.
+fn count_syllables(word: &str) -> usize {
+    const VOWELS: &str = "aeiouyàâäéèêëîïôöùûüÿæœ";
+
+    let mut syllables = 0;
+    let mut previous_vowel = false;
+    for c in word.to_lowercase().chars() {
+        let vowel = VOWELS.contains(c);
+        if vowel && !previous_vowel {
+            syllables += 1;
+        }
+        previous_vowel = vowel;
+    }
+
+    syllables
+}
+
+fn change_case(word: &str, case: WordCase, first: bool) -> String {
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(c) => c.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    };
+
+    match case {
+        WordCase::Unchanged => word.to_string(),
+        WordCase::Lower => word.to_lowercase(),
+        WordCase::Upper => word.to_uppercase(),
+        WordCase::Capitalize if first => capitalize(word),
+        WordCase::Capitalize => word.to_string(),
+        WordCase::Title => capitalize(word),
     }
 }
 
@@ -101,23 +476,238 @@ struct QuoteSourceResponse {
     quote: HashMap<String, String>,
 }
 
-/// [TextSource] for generating a random word.
-#[derive(Default)]
-pub struct QuoteSource;
+/// [TextSource] for a random quote fetched from the network.
+///
+/// Fetched quotes are cached so they can later be used offline. When the fetched
+/// quote does not match the filters, a matching local quote is used instead.
+pub struct QuoteSource<'a> {
+    filters: QuoteOptions,
+    preferences: Preferences,
+    rng: RefCell<&'a mut StdRng>,
+}
+
+impl<'a> QuoteSource<'a> {
+    pub fn new(filters: QuoteOptions, preferences: Preferences, rng: &'a mut StdRng) -> Self {
+        Self {
+            filters,
+            preferences,
+            rng: RefCell::new(rng),
+        }
+    }
+}
 
-impl TextSource for QuoteSource {
+impl TextSource for QuoteSource<'_> {
     /// Returns quote as ["<quote>", "<author>"]
-    fn source_text(&self) -> Vec<String> {
+    fn source_text(&self) -> Result<Vec<String>> {
         let url = "https://zenquotes.io?api=random";
 
-        let response = reqwest::blocking::get(url)
-            .unwrap()
-            .json::<QuoteSourceResponse>()
-            .unwrap();
+        let response = reqwest::blocking::get(url)?.json::<QuoteSourceResponse>()?;
+        let field = |name: &str| {
+            response
+                .quote
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::Network(format!("Quote response is missing \"{name}\"")))
+        };
+        let quote = Quote {
+            text: field("q")?,
+            author: field("a")?,
+            tags: vec![],
+            source: None,
+        };
+
+        if let Err(e) = cache_quote(&quote) {
+            eprintln!("{e}. The quote was not cached.");
+        }
+
+        if self.filters.matches(&quote) {
+            Ok(vec![quote.text, quote.author])
+        } else {
+            let mut rng = self.rng.borrow_mut();
+            OfflineQuoteSource::new(self.filters.clone(), self.preferences.clone(), &mut rng)
+                .source_text()
+        }
+    }
+}
+
+/// [TextSource] for a random quote among the bundled and cached quotes, which
+/// does not require a network connection. Liked authors are picked more often.
+pub struct OfflineQuoteSource<'a> {
+    filters: QuoteOptions,
+    preferences: Preferences,
+    rng: RefCell<&'a mut StdRng>,
+}
+
+impl<'a> OfflineQuoteSource<'a> {
+    pub fn new(filters: QuoteOptions, preferences: Preferences, rng: &'a mut StdRng) -> Self {
+        Self {
+            filters,
+            preferences,
+            rng: RefCell::new(rng),
+        }
+    }
+}
+
+impl TextSource for OfflineQuoteSource<'_> {
+    /// Returns quote as ["<quote>", "<author>"]
+    fn source_text(&self) -> Result<Vec<String>> {
+        let quote = random_quote(
+            &local_quotes()?,
+            &self.filters,
+            &self.preferences,
+            *self.rng.borrow_mut(),
+        )?;
+        Ok(vec![quote.text, quote.author])
+    }
+}
+
+/// [TextSource] for a quote read from a user's file.
+pub struct QuoteFileSource<'a> {
+    path: PathBuf,
+    format: QuoteFileFormat,
+    order: QuoteOrder,
+    filters: QuoteOptions,
+    rng: RefCell<&'a mut StdRng>,
+}
+
+impl<'a> QuoteFileSource<'a> {
+    pub fn new(
+        path: PathBuf,
+        format: QuoteFileFormat,
+        order: QuoteOrder,
+        filters: QuoteOptions,
+        rng: &'a mut StdRng,
+    ) -> Self {
+        Self {
+            path,
+            format,
+            order,
+            filters,
+            rng: RefCell::new(rng),
+        }
+    }
+}
+
+impl TextSource for QuoteFileSource<'_> {
+    /// Returns quote as ["<quote>", "<author>"], followed by "<source>" when the
+    /// quote has one, without the author when the file has none
+    fn source_text(&self) -> Result<Vec<String>> {
+        let quote = select_quote(
+            &self.path,
+            self.format,
+            self.order,
+            &self.filters,
+            *self.rng.borrow_mut(),
+        )?;
+        let text = [Some(quote.text), Some(quote.author), quote.source]
+            .into_iter()
+            .flatten()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+
+        Ok(text)
+    }
+}
+
+/// [TextSource] for the date and time.
+pub struct ClockSource {
+    formats: Vec<String>,
+    time_zone: Option<Tz>,
+    time: DateTime<Utc>,
+}
+
+impl ClockSource {
+    pub fn new(formats: Vec<String>, time_zone: Option<Tz>, time: DateTime<Utc>) -> Self {
+        Self {
+            formats,
+            time_zone,
+            time,
+        }
+    }
+}
+
+impl TextSource for ClockSource {
+    /// Returns one line per format
+    fn source_text(&self) -> Result<Vec<String>> {
+        self.formats
+            .iter()
+            .map(|format| {
+                let items: Vec<Item> = StrftimeItems::new(format).collect();
+                if items.contains(&Item::Error) {
+                    return Err(Error::Config(format!("Invalid clock format \"{format}\"")));
+                }
+                let line = match self.time_zone {
+                    Some(time_zone) => self
+                        .time
+                        .with_timezone(&time_zone)
+                        .format_with_items(items.into_iter())
+                        .to_string(),
+                    None => self
+                        .time
+                        .with_timezone(&Local)
+                        .format_with_items(items.into_iter())
+                        .to_string(),
+                };
+                Ok(line)
+            })
+            .collect()
+    }
+}
+
+/// [TextSource] for the word of the day, picked from a local dictionary.
+pub struct WordOfTheDaySource {
+    dictionary: PathBuf,
+    date: NaiveDate,
+}
+
+impl WordOfTheDaySource {
+    pub fn new(dictionary: PathBuf, date: NaiveDate) -> Self {
+        Self { dictionary, date }
+    }
+}
+
+impl TextSource for WordOfTheDaySource {
+    /// Returns ["<word>", "<part of speech> <pronunciation>", "<definition>"],
+    /// without the lines the dictionary has no information for
+    fn source_text(&self) -> Result<Vec<String>> {
+        let entries = read_dictionary(&self.dictionary)?;
+        let entry = entry_of_the_day(&entries, self.date)
+            .ok_or_else(|| Error::Config("The dictionary is empty".to_string()))?;
+
+        let details = [&entry.part_of_speech, &entry.pronunciation]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<&str>>()
+            .join("  ");
+        let text = [entry.word.clone(), details, entry.definition.clone()]
+            .into_iter()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+
+        Ok(text)
+    }
+}
+
+/// [TextSource] for the items of a todo list.
+pub struct TodoSource {
+    options: TodoOptions,
+    today: NaiveDate,
+}
+
+impl TodoSource {
+    pub fn new(options: TodoOptions, today: NaiveDate) -> Self {
+        Self { options, today }
+    }
+}
 
-        vec![
-            response.quote.get(&"q".to_string()).unwrap().to_owned(),
-            response.quote.get(&"a".to_string()).unwrap().to_owned(),
-        ]
+impl TextSource for TodoSource {
+    /// Returns one checklist line per item, starting with "[ ] " or "[x] "
+    fn source_text(&self) -> Result<Vec<String>> {
+        let items = read_todo_file(&self.options.path)?;
+        Ok(filter_items(items, &self.options, self.today)
+            .iter()
+            .map(TodoItem::checklist_line)
+            .collect())
     }
 }
@@ -0,0 +1,189 @@
+use std::f64::consts::PI;
+
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc,
+};
+use dotenv::dotenv;
+use serde::{Deserialize, Serialize};
+
+/// Zenith of the sun's center at sunrise and sunset, accounting for atmospheric
+/// refraction and the radius of the sun.
+const SUNRISE_ZENITH: f64 = 90.833;
+/// Zenith of the sun at the start of morning and the end of evening civil twilight.
+const CIVIL_TWILIGHT_ZENITH: f64 = 96.0;
+
+/// Position on Earth, in degrees. North and east are positive.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Location {
+    /// Reads the location from the `MACGROUND_LATITUDE` and `MACGROUND_LONGITUDE`
+    /// environment variables, which can also be set in a .env file.
+    pub fn from_env() -> Option<Location> {
+        dotenv().ok();
+
+        let coordinate = |name: &str| std::env::var(name).ok()?.trim().parse::<f64>().ok();
+        Some(Location {
+            latitude: coordinate("MACGROUND_LATITUDE")?,
+            longitude: coordinate("MACGROUND_LONGITUDE")?,
+        })
+    }
+}
+
+/// Daily events of the sun.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SunEvent {
+    /// Start of morning civil twilight
+    Dawn,
+    Sunrise,
+    /// Sun at its highest point
+    Noon,
+    Sunset,
+    /// End of evening civil twilight
+    Dusk,
+}
+
+impl SunEvent {
+    pub const ALL: [SunEvent; 5] = [
+        SunEvent::Dawn,
+        SunEvent::Sunrise,
+        SunEvent::Noon,
+        SunEvent::Sunset,
+        SunEvent::Dusk,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SunEvent::Dawn => "dawn",
+            SunEvent::Sunrise => "sunrise",
+            SunEvent::Noon => "noon",
+            SunEvent::Sunset => "sunset",
+            SunEvent::Dusk => "dusk",
+        }
+    }
+}
+
+/// Position of the sun in the sky.
+#[derive(Debug, Clone, Copy)]
+pub struct SunPosition {
+    /// Angle of the sun above the horizon in degrees, negative below it.
+    pub elevation: f64,
+    /// Whether the sun is rising (before solar noon) or setting.
+    pub rising: bool,
+}
+
+/// Declination of the sun and the equation of time (in minutes) at a time,
+/// using the NOAA approximations.
+fn solar_parameters(time: &DateTime<Utc>) -> (f64, f64) {
+    let hour = time.hour() as f64 + time.minute() as f64 / 60.0;
+    let year_length = if NaiveDate::from_yo_opt(time.year(), 366).is_some() {
+        366.0
+    } else {
+        365.0
+    };
+    // Fractional year in radians
+    let gamma = 2.0 * PI / year_length * (time.ordinal0() as f64 + (hour - 12.0) / 24.0);
+
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * gamma.cos()
+            - 0.032077 * gamma.sin()
+            - 0.014615 * (2.0 * gamma).cos()
+            - 0.040849 * (2.0 * gamma).sin());
+    let declination = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin()
+        - 0.006758 * (2.0 * gamma).cos()
+        + 0.000907 * (2.0 * gamma).sin()
+        - 0.002697 * (3.0 * gamma).cos()
+        + 0.00148 * (3.0 * gamma).sin();
+
+    (declination, equation_of_time)
+}
+
+/// Computes the position of the sun at a location and time.
+pub fn sun_position(location: &Location, time: &DateTime<Utc>) -> SunPosition {
+    let (declination, equation_of_time) = solar_parameters(time);
+    let latitude = location.latitude.to_radians();
+
+    let minutes = time.hour() as f64 * 60.0 + time.minute() as f64 + time.second() as f64 / 60.0;
+    let true_solar_time = minutes + equation_of_time + 4.0 * location.longitude;
+    let hour_angle = (true_solar_time / 4.0 - 180.0).to_radians();
+
+    let cos_zenith =
+        latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+    let zenith = cos_zenith.clamp(-1.0, 1.0).acos().to_degrees();
+
+    SunPosition {
+        elevation: 90.0 - zenith,
+        // The hour angle is negative in the morning, wrapped to (-180, 180]
+        rising: hour_angle.sin() < 0.0,
+    }
+}
+
+/// Time of a sun event on a date (in UTC) at a location. Returns [None] when the
+/// event does not happen that day, e.g. sunset during a polar day.
+pub fn sun_event(location: &Location, date: NaiveDate, event: SunEvent) -> Option<DateTime<Utc>> {
+    // Evaluate the sun around the location's solar noon
+    let approximate_noon = Utc.from_utc_datetime(&date.and_hms_opt(12, 0, 0)?)
+        - Duration::minutes((4.0 * location.longitude) as i64);
+    let (declination, equation_of_time) = solar_parameters(&approximate_noon);
+    let noon = 720.0 - 4.0 * location.longitude - equation_of_time;
+
+    let offset = match event {
+        SunEvent::Noon => 0.0,
+        SunEvent::Sunrise | SunEvent::Sunset => hour_angle(location, declination, SUNRISE_ZENITH)?,
+        SunEvent::Dawn | SunEvent::Dusk => {
+            hour_angle(location, declination, CIVIL_TWILIGHT_ZENITH)?
+        }
+    };
+    let minutes = match event {
+        SunEvent::Dawn | SunEvent::Sunrise => noon - 4.0 * offset,
+        SunEvent::Noon => noon,
+        SunEvent::Sunset | SunEvent::Dusk => noon + 4.0 * offset,
+    };
+
+    let midnight = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0)?);
+    Some(midnight + Duration::seconds((minutes * 60.0).round() as i64))
+}
+
+/// Hour angle in degrees at which the sun reaches `zenith`.
+fn hour_angle(location: &Location, declination: f64, zenith: f64) -> Option<f64> {
+    let latitude = location.latitude.to_radians();
+    let cos_hour_angle = zenith.to_radians().cos() / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+
+    if (-1.0..=1.0).contains(&cos_hour_angle) {
+        Some(cos_hour_angle.acos().to_degrees())
+    } else {
+        None
+    }
+}
+
+/// Local time of a sun event on a local date.
+pub fn local_sun_event(
+    location: &Location,
+    date: NaiveDate,
+    event: SunEvent,
+) -> Option<NaiveDateTime> {
+    sun_event(location, date, event).map(|time| time.with_timezone(&Local).naive_local())
+}
+
+/// Prints the position of the sun and today's sun events.
+pub fn print_sun_report(location: &Location) {
+    let sun = sun_position(location, &Utc::now());
+    println!(
+        "Elevation: {:.1}° ({})",
+        sun.elevation,
+        if sun.rising { "rising" } else { "setting" }
+    );
+
+    let today = Local::now().date_naive();
+    for event in SunEvent::ALL {
+        match local_sun_event(location, today, event) {
+            Some(time) => println!("{:<8} {}", event.name(), time.format("%H:%M")),
+            None => println!("{:<8} -", event.name()),
+        }
+    }
+}
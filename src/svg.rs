@@ -0,0 +1,72 @@
+use std::path::Path;
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{ImageBuffer, ImageError, RgbaImage};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg;
+
+use crate::error::{Error, Result};
+use crate::shapes::unpremultiply;
+
+/// Parsed SVG file.
+pub struct Svg {
+    tree: usvg::Tree,
+}
+
+impl Svg {
+    /// Reads an SVG file. Images it links to are resolved relative to it, and its
+    /// text is drawn with the system fonts.
+    pub fn open(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).map_err(|e| {
+            Error::Config(format!("Failed to read SVG file {}. {e}", path.display()))
+        })?;
+
+        let mut options = usvg::Options {
+            resources_dir: path.parent().map(Path::to_path_buf),
+            ..Default::default()
+        };
+        options.fontdb_mut().load_system_fonts();
+
+        let tree = usvg::Tree::from_data(&data, &options).map_err(|e| {
+            Error::Decode(ImageError::Decoding(DecodingError::new(
+                ImageFormatHint::Name(format!("SVG {}", path.display())),
+                e,
+            )))
+        })?;
+
+        Ok(Self { tree })
+    }
+
+    /// Intrinsic width and height of the SVG.
+    pub fn size(&self) -> (f32, f32) {
+        let size = self.tree.size();
+        (size.width(), size.height())
+    }
+
+    /// Rasterizes the SVG stretched to `width` by `height` pixels.
+    pub fn render(&self, width: u32, height: u32) -> Result<RgbaImage> {
+        let mut pixmap = Pixmap::new(width.max(1), height.max(1))
+            .ok_or_else(|| Error::Config(format!("Invalid SVG size {width}x{height}")))?;
+        let (svg_width, svg_height) = self.size();
+        let transform = Transform::from_scale(
+            pixmap.width() as f32 / svg_width,
+            pixmap.height() as f32 / svg_height,
+        );
+        resvg::render(&self.tree, transform, &mut pixmap.as_mut());
+
+        let pixels = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| unpremultiply(*pixel))
+            .collect();
+        ImageBuffer::from_vec(pixmap.width(), pixmap.height(), pixels)
+            .ok_or_else(|| Error::Config("Failed to convert the rendered SVG".to_string()))
+    }
+
+    /// Rasterizes the SVG scaled to `width` pixels wide, keeping its aspect ratio.
+    pub fn render_width(&self, width: u32) -> Result<RgbaImage> {
+        let (svg_width, svg_height) = self.size();
+        let height = (width as f32 * svg_height / svg_width).round() as u32;
+        self.render(width, height)
+    }
+}
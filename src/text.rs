@@ -1,4 +1,3 @@
-#[allow(unused_variables)]
 use lazy_static::lazy_static;
 // use std::path::PathBuf;
 use std::sync::Mutex;
@@ -8,6 +7,7 @@ use std::{collections::HashMap, default::Default, path::Path};
 use glyph_brush_layout::{ab_glyph::*, *};
 use image::{Pixel, Rgba};
 
+use crate::error::{Error, Result};
 use crate::BackgroundImage;
 
 lazy_static! {
@@ -37,15 +37,26 @@ impl FontLoader {
         loader
     }
 
-    pub fn load_font(&self, name: String, path: &Path) -> () {
+    pub fn load_font(&self, name: String, path: &Path) -> Result<()> {
+        let font_error = |reason: String| Error::FontLoad(format!("{}: {reason}", path.display()));
+
+        let bytes = std::fs::read(path).map_err(|e| font_error(e.to_string()))?;
         // Create a static reference to the font using Vec<_>.leak()
-        let bytes = std::fs::read(path).unwrap().leak();
-        let font_ref = FontRef::try_from_slice(bytes).unwrap();
+        let font_ref =
+            FontRef::try_from_slice(bytes.leak()).map_err(|e| font_error(e.to_string()))?;
         self.fonts.lock().unwrap().insert(name, font_ref);
+
+        Ok(())
     }
 
+    /// Returns the font with the given name, or the default font if it was never loaded.
     pub fn font(&self, name: String) -> FontRef<'_> {
-        self.fonts.lock().unwrap().get(&name).unwrap().clone()
+        let fonts = self.fonts.lock().unwrap();
+        fonts
+            .get(&name)
+            .or_else(|| fonts.get("default"))
+            .unwrap()
+            .clone()
     }
 }
 
@@ -103,15 +114,39 @@ pub fn draw_textbox(image: &mut BackgroundImage, textbox: TextBox, screen_x: u32
     draw_text(image, glyphs, textbox.style, screen_x, screen_y);
 }
 
+/// Draws textboxes stacked from top to bottom, `spacing` pixels apart, with the
+/// stack centered at the given position. Textboxes without text take no space.
+pub fn draw_textbox_stack(
+    image: &mut BackgroundImage,
+    textboxes: Vec<TextBox>,
+    spacing: u32,
+    screen_x: u32,
+    screen_y: u32,
+) {
+    let textboxes: Vec<TextBox> = textboxes
+        .into_iter()
+        .filter(|textbox| !textbox.text.trim().is_empty())
+        .collect();
+    let total_height = textboxes.iter().map(|textbox| textbox.height).sum::<u32>()
+        + spacing * textboxes.len().saturating_sub(1) as u32;
+
+    let mut top = screen_y.saturating_sub(total_height / 2);
+    for textbox in textboxes {
+        let height = textbox.height;
+        draw_textbox(image, textbox, screen_x, top + height / 2);
+        top += height + spacing;
+    }
+}
+
 /// Generates outlined glyphs positioned at (0, 0) on the screen
 pub fn generate_textbox_glyphs(textbox: &TextBox) -> Vec<OutlinedGlyph> {
     let text_style = &textbox.style;
-    let font_ref = (*FONT_LOADER).font("default".to_string());
+    let font_ref = (*FONT_LOADER).font(text_style.font.clone());
 
     match text_style.size {
         TextSize::PxScale(scale) => {
             let glyphs: Vec<SectionGlyph> = text_style.layout.calculate_glyphs(
-                &[font_ref.clone()],
+                std::slice::from_ref(&font_ref),
                 &SectionGeometry {
                     bounds: (textbox.width as f32, textbox.height as f32),
                     ..Default::default()
@@ -120,7 +155,6 @@ pub fn generate_textbox_glyphs(textbox: &TextBox) -> Vec<OutlinedGlyph> {
                     font_id: FontId(0),
                     text: textbox.text.as_str(),
                     scale: PxScale::from(scale), // Pixel-height of the text
-                    ..Default::default()
                 }],
             );
 
@@ -134,12 +168,14 @@ pub fn generate_textbox_glyphs(textbox: &TextBox) -> Vec<OutlinedGlyph> {
 
             outlined_glyphs
         }
+        // No size fills the box without any glyph to outline
+        TextSize::FillParent if textbox.text.trim().is_empty() => vec![],
         TextSize::FillParent => {
             // Attempts to draw the text with the given text size. If it cannot draw it within the bounds,
             // None is returned.
             let attempt_text_size = |text_size: f32| -> Option<Vec<OutlinedGlyph>> {
                 let glyphs: Vec<SectionGlyph> = text_style.layout.calculate_glyphs(
-                    &[font_ref.clone()],
+                    std::slice::from_ref(&font_ref),
                     &SectionGeometry {
                         bounds: (textbox.width as f32, textbox.height as f32),
                         ..Default::default()
@@ -148,7 +184,6 @@ pub fn generate_textbox_glyphs(textbox: &TextBox) -> Vec<OutlinedGlyph> {
                         font_id: FontId(0),
                         text: textbox.text.as_str(),
                         scale: PxScale::from(text_size), // Pixel-height of the text
-                        ..Default::default()
                     }],
                 );
 
@@ -156,7 +191,7 @@ pub fn generate_textbox_glyphs(textbox: &TextBox) -> Vec<OutlinedGlyph> {
                 for section_glyph in glyphs {
                     let raw_glyph = section_glyph.glyph;
                     if let Some(glyph) = font_ref.outline_glyph(raw_glyph.clone()) {
-                        if within_bounds(&glyph, &textbox) {
+                        if within_bounds(&glyph, textbox) {
                             outlined_glyphs.push(glyph);
                         } else {
                             return None;
@@ -192,13 +227,10 @@ pub fn within_bounds(glyph: &OutlinedGlyph, textbox: &TextBox) -> bool {
     let half_height = textbox.height as f32 / 2.0;
     let bounds = glyph.px_bounds();
 
-    if bounds.min.x < -half_width || bounds.max.x >= half_width {
-        false
-    } else if bounds.min.y < -half_height || bounds.max.y >= half_height {
-        false
-    } else {
-        true
-    }
+    let within_x = bounds.min.x >= -half_width && bounds.max.x < half_width;
+    let within_y = bounds.min.y >= -half_height && bounds.max.y < half_height;
+
+    within_x && within_y
 }
 
 /// Draws text to the screen at a given screen position (top-left coordinates)
@@ -0,0 +1,302 @@
+use std::path::Path;
+
+use chrono::{Duration, NaiveDate};
+use glyph_brush_layout::{BuiltInLineBreaker, HorizontalAlign, Layout, VerticalAlign};
+
+use crate::args::TodoOptions;
+use crate::display::Region;
+use crate::error::{Error, Result};
+use crate::text::{draw_text, generate_textbox_glyphs, TextBox, TextConfig, TextSize};
+use crate::BackgroundImage;
+
+/// Prefix of the lines of a checklist for unfinished items.
+pub const UNCHECKED: &str = "[ ] ";
+/// Prefix of the lines of a checklist for done items.
+pub const CHECKED: &str = "[x] ";
+/// Keys of the todo.txt tags removed from descriptions, besides "due".
+/// Other words with a colon, such as URLs, are part of the description.
+const TAG_KEYS: [&str; 6] = ["t", "rec", "pri", "h", "id", "dep"];
+
+/// Item of a todo.txt file or of a Markdown checklist.
+#[derive(Debug, Clone)]
+pub struct TodoItem {
+    /// Description without the completion mark, dates, priority and known key:value tags
+    pub text: String,
+    pub done: bool,
+    /// "A" is the highest priority
+    pub priority: Option<char>,
+    /// Contexts such as "@work"
+    pub contexts: Vec<String>,
+    pub due: Option<NaiveDate>,
+}
+
+impl TodoItem {
+    /// Line of a checklist, see [CHECKED] and [UNCHECKED].
+    pub fn checklist_line(&self) -> String {
+        let mark = if self.done { CHECKED } else { UNCHECKED };
+        format!("{mark}{}", self.text)
+    }
+}
+
+/// Reads the items of a todo.txt file, or the "- [ ]" items of a Markdown file.
+pub fn read_todo_file(path: &Path) -> Result<Vec<TodoItem>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("Failed to read todo list {}. {e}", path.display())))?;
+
+    let is_markdown = path.extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown")
+    });
+    let items = if is_markdown {
+        contents.lines().filter_map(parse_markdown_item).collect()
+    } else {
+        contents.lines().filter_map(parse_todo_txt_item).collect()
+    };
+
+    Ok(items)
+}
+
+/// Parses a todo.txt line: "x (A) 2026-10-18 Call Bob +project @phone due:2026-10-20".
+fn parse_todo_txt_item(line: &str) -> Option<TodoItem> {
+    let mut words = line.split_whitespace().peekable();
+    let done = words.next_if_eq(&"x").is_some();
+
+    let priority = words
+        .next_if(|word| is_priority(word))
+        .and_then(|word| word.chars().nth(1));
+    // Completion and creation dates
+    while words
+        .next_if(|word| NaiveDate::parse_from_str(word, "%Y-%m-%d").is_ok())
+        .is_some()
+    {}
+
+    let mut item = parse_description(&words.collect::<Vec<&str>>().join(" "))?;
+    item.done = done;
+    item.priority = priority;
+    Some(item)
+}
+
+fn is_priority(word: &str) -> bool {
+    let chars: Vec<char> = word.chars().collect();
+    chars.len() == 3 && chars[0] == '(' && chars[1].is_ascii_uppercase() && chars[2] == ')'
+}
+
+/// Parses a Markdown checklist item: "- [ ] Call Bob @phone due:2026-10-20".
+fn parse_markdown_item(line: &str) -> Option<TodoItem> {
+    let line = line.trim_start();
+    let rest = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("+ "))?;
+    let done = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+
+    let mut item = parse_description(&rest[3..])?;
+    item.done = done;
+    Some(item)
+}
+
+/// Reads the contexts and due date of a description, and removes its known
+/// key:value tags.
+fn parse_description(description: &str) -> Option<TodoItem> {
+    let mut contexts = vec![];
+    let mut due = None;
+    let mut words = vec![];
+    for word in description.split_whitespace() {
+        if let Some(date) = word.strip_prefix("due:") {
+            due = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok();
+            continue;
+        }
+        let is_tag = word.split_once(':').is_some_and(|(key, value)| {
+            TAG_KEYS.contains(&key) && !value.is_empty() && !value.contains('/')
+        });
+        if is_tag {
+            continue;
+        }
+        if word.len() > 1 && word.starts_with('@') {
+            contexts.push(word.to_string());
+        }
+        words.push(word);
+    }
+
+    if words.is_empty() {
+        return None;
+    }
+    Some(TodoItem {
+        text: words.join(" "),
+        done: false,
+        priority: None,
+        contexts,
+        due,
+    })
+}
+
+/// Items passing the filters of the options, unfinished items first, then by
+/// priority and due date.
+pub fn filter_items(
+    items: Vec<TodoItem>,
+    options: &TodoOptions,
+    today: NaiveDate,
+) -> Vec<TodoItem> {
+    let mut items: Vec<TodoItem> = items
+        .into_iter()
+        .filter(|item| !(options.hide_done && item.done))
+        .filter(|item| {
+            options
+                .priority
+                .is_none_or(|lowest| item.priority.is_some_and(|priority| priority <= lowest))
+        })
+        .filter(|item| {
+            options.context.as_ref().is_none_or(|context| {
+                let context = format!("@{}", context.trim_start_matches('@'));
+                item.contexts
+                    .iter()
+                    .any(|item_context| item_context.eq_ignore_ascii_case(&context))
+            })
+        })
+        .filter(|item| {
+            options.due_within.is_none_or(|days| {
+                item.due
+                    .is_some_and(|due| due <= today + Duration::days(days as i64))
+            })
+        })
+        .collect();
+
+    items.sort_by_key(|item| {
+        (
+            item.done,
+            item.priority.unwrap_or('~'),
+            item.due.unwrap_or(NaiveDate::MAX),
+        )
+    });
+    items.truncate(options.limit);
+    items
+}
+
+/// Draws checklist lines as a list centered in a region, with a checkbox before
+/// every item and done items struck through.
+pub fn draw_checklist(
+    image: &mut BackgroundImage,
+    lines: &[String],
+    region: &Region,
+    style: &TextConfig,
+) {
+    let line_height = (region.height / 18).max(8);
+    let box_size = line_height / 2;
+    let thickness = (line_height / 24).max(2);
+    let width = region.width / 2;
+    let (center_x, center_y) = region.center();
+    let left = center_x.saturating_sub(width / 2);
+    let top = center_y.saturating_sub(line_height * lines.len() as u32 / 2);
+
+    let text_style = TextConfig {
+        size: TextSize::PxScale(line_height as f32 * 0.5),
+        layout: Layout::Wrap {
+            h_align: HorizontalAlign::Left,
+            v_align: VerticalAlign::Center,
+            line_breaker: BuiltInLineBreaker::UnicodeLineBreaker,
+        },
+        ..style.clone()
+    };
+    let color = style.color;
+
+    for (index, line) in lines.iter().enumerate() {
+        let (done, text) = match (line.strip_prefix(CHECKED), line.strip_prefix(UNCHECKED)) {
+            (Some(text), _) => (true, text),
+            (_, Some(text)) => (false, text),
+            _ => (false, line.as_str()),
+        };
+        let middle = top + index as u32 * line_height + line_height / 2;
+        let box_top = middle - box_size / 2;
+
+        // Checkbox outline, filled when done
+        let edges = [
+            (left, box_top, box_size, thickness),
+            (left, box_top + box_size - thickness, box_size, thickness),
+            (left, box_top, thickness, box_size),
+            (left + box_size - thickness, box_top, thickness, box_size),
+        ];
+        for (x, y, width, height) in edges {
+            image.fill_rect(
+                &Region {
+                    x,
+                    y,
+                    width,
+                    height,
+                },
+                &color,
+            );
+        }
+        if done {
+            let inset = thickness * 2;
+            image.fill_rect(
+                &Region {
+                    x: left + inset,
+                    y: box_top + inset,
+                    width: box_size - 2 * inset,
+                    height: box_size - 2 * inset,
+                },
+                &color,
+            );
+        }
+
+        let text_left = left + box_size * 2;
+        let textbox = TextBox {
+            text: text.to_string(),
+            width: width - box_size * 2,
+            height: line_height,
+            style: text_style.clone(),
+        };
+        let glyphs = generate_textbox_glyphs(&textbox);
+        let text_width = glyphs
+            .iter()
+            .map(|glyph| glyph.px_bounds().max.x)
+            .fold(0.0, f32::max) as u32;
+        draw_text(image, glyphs, textbox.style, text_left, middle);
+
+        if done {
+            image.fill_rect(
+                &Region {
+                    x: text_left,
+                    y: middle - thickness / 2,
+                    width: text_width,
+                    height: thickness,
+                },
+                &color,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_known_tags() {
+        let item = parse_todo_txt_item(
+            "(B) 2026-10-18 Water plants @home t:2026-10-19 rec:1w due:2026-10-20",
+        )
+        .unwrap();
+        assert_eq!(item.text, "Water plants @home");
+        assert_eq!(item.priority, Some('B'));
+        assert_eq!(item.contexts, ["@home"]);
+        assert_eq!(item.due, NaiveDate::from_ymd_opt(2026, 10, 20));
+    }
+
+    #[test]
+    fn keeps_urls_and_other_colons() {
+        let item =
+            parse_todo_txt_item("Read https://example.com/post Re:meeting at 10:30").unwrap();
+        assert_eq!(
+            item.text,
+            "Read https://example.com/post Re:meeting at 10:30"
+        );
+
+        let item = parse_markdown_item("- [x] Open mailto:bob@example.com").unwrap();
+        assert!(item.done);
+        assert_eq!(item.text, "Open mailto:bob@example.com");
+    }
+}
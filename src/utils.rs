@@ -1,12 +1,233 @@
+use std::io::Read;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
 use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use directories::ProjectDirs;
 
-pub fn application_data_path() -> PathBuf {
-    let project_dirs = ProjectDirs::from("com", "", "macground").unwrap();
+use crate::error::Error;
+
+pub fn application_data_path() -> Result<PathBuf, Error> {
+    let project_dirs = ProjectDirs::from("com", "", "macground")
+        .ok_or_else(|| Error::Config("Failed to find the home directory".to_string()))?;
     let data_path = project_dirs.data_dir().to_path_buf();
 
-    std::fs::create_dir_all(&data_path).unwrap();
+    std::fs::create_dir_all(&data_path)?;
+
+    Ok(data_path)
+}
+
+/// Parses a duration such as "45s", "30m", "2h", "1d" or "1h30m".
+pub fn parse_duration(raw_duration: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration {raw_duration}");
+
+    let mut seconds: u64 = 0;
+    let mut value = String::new();
+    for c in raw_duration.trim().chars() {
+        if c.is_ascii_digit() {
+            value.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let count: u64 = value.parse().map_err(|_| invalid())?;
+        seconds = count
+            .checked_mul(unit)
+            .and_then(|duration| seconds.checked_add(duration))
+            .ok_or_else(invalid)?;
+        value.clear();
+    }
+
+    if !value.is_empty() || seconds == 0 {
+        return Err(invalid());
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Reads all of stdin, without the surrounding whitespace.
+pub fn read_stdin() -> Result<String, Error> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+
+    Ok(input.trim().to_string())
+}
+
+/// Time the output of a finished command is still read for, when processes it
+/// started in the background keep its pipes open.
+const OUTPUT_GRACE: Duration = Duration::from_millis(200);
+
+/// Runs a command with the platform's shell and returns its output, without the
+/// surrounding whitespace. The command and the processes it started are killed if
+/// it runs for longer than `timeout`. Processes it leaves running in the
+/// background after it exits are left alone.
+pub fn run_command(command: &str, timeout: Duration) -> Result<String, Error> {
+    run_command_with_env(command, &[], timeout)
+}
+
+/// Runs a command like [run_command], with extra environment variables.
+pub fn run_command_with_env(
+    command: &str,
+    env: &[(String, String)],
+    timeout: Duration,
+) -> Result<String, Error> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    // In its own process group, so the processes it starts can be killed with it
+    #[cfg(unix)]
+    shell.process_group(0);
+    let mut child = shell
+        .arg(command)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Command(format!("Failed to run \"{command}\". {e}")))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .map(|pipe| OutputReader::spawn(Box::new(pipe)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|pipe| OutputReader::spawn(Box::new(pipe)));
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() >= timeout {
+            kill_process_group(&mut child);
+            return Err(Error::Command(format!(
+                "\"{command}\" did not finish within {}s",
+                timeout.as_secs()
+            )));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+
+    // Background processes holding the pipes would make the readers wait for them
+    let deadline = Instant::now() + OUTPUT_GRACE;
+    let done = |reader: &Option<OutputReader>| reader.as_ref().is_none_or(OutputReader::is_done);
+    while Instant::now() < deadline && !(done(&stdout) && done(&stderr)) {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    let output =
+        |reader: Option<OutputReader>| reader.map(OutputReader::output).unwrap_or_default();
+    let (stdout, stderr) = (output(stdout), output(stderr));
+    if !status.success() {
+        return Err(Error::Command(format!(
+            "\"{command}\" exited with {status}. {}",
+            stderr.trim()
+        )));
+    }
+
+    Ok(stdout.trim().to_string())
+}
+
+/// Output of a pipe, read while the command runs so it cannot block on a full pipe.
+struct OutputReader {
+    output: Arc<Mutex<Vec<u8>>>,
+    thread: JoinHandle<()>,
+}
+
+impl OutputReader {
+    fn spawn(mut pipe: Box<dyn Read + Send>) -> Self {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let buffer = Arc::clone(&output);
+        let thread = std::thread::spawn(move || {
+            let mut chunk = [0; 4096];
+            while let Ok(count @ 1..) = pipe.read(&mut chunk) {
+                buffer
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .extend_from_slice(&chunk[..count]);
+            }
+        });
+
+        Self { output, thread }
+    }
+
+    /// Whether the pipe was closed by every process writing to it.
+    fn is_done(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Output read so far.
+    fn output(self) -> String {
+        let output = self.output.lock().unwrap_or_else(PoisonError::into_inner);
+        String::from_utf8_lossy(&output).to_string()
+    }
+}
+
+/// Kills a command started by [run_command_with_env] along with the processes it started.
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", child.id())])
+        .stderr(Stdio::null())
+        .status()
+        .ok();
+    child.kill().ok();
+    child.wait().ok();
+}
+
+/// Quotes an argument for the platform's shell.
+pub fn shell_quote(argument: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{argument}\"")
+    } else {
+        format!("'{}'", argument.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn background_processes_do_not_hold_the_output() {
+        let start = Instant::now();
+        let output = run_command("sleep 5 & echo hi", Duration::from_secs(2)).unwrap();
+        assert_eq!(output, "hi");
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn timeout_kills_the_processes_started_by_the_command() {
+        let marker = std::env::temp_dir().join(format!("macground-timeout-{}", std::process::id()));
+        let command = format!("(sleep 1; touch {}) & sleep 5", marker.display());
+        let start = Instant::now();
+        assert!(run_command(&command, Duration::from_millis(300)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        std::thread::sleep(Duration::from_millis(1200));
+        assert!(!marker.exists());
+    }
 
-    data_path
+    #[test]
+    fn failing_command_reports_its_errors() {
+        let error = run_command("echo broken >&2; exit 3", Duration::from_secs(2)).unwrap_err();
+        assert!(error.to_string().contains("broken"), "{error}");
+    }
 }